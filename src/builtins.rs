@@ -0,0 +1,929 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use indexmap::IndexMap;
+
+use crate::env::Env;
+use crate::eval::{apply, eval_obj, EvalError, EvalErrorKind};
+use crate::number::{self, Number};
+use crate::object::*;
+use crate::symbol::Symbol;
+
+//  所有内置函数，创建全局 env 时绑定
+const BUILTINS: &[Builtin] = &[
+    Builtin::new("list", Arity::AtLeast(0), list),
+    Builtin::new("car", Arity::Exact(1), car),
+    Builtin::new("cdr", Arity::Exact(1), cdr),
+    Builtin::new("length", Arity::Exact(1), length),
+    Builtin::new("null?", Arity::Exact(1), is_null),
+    Builtin::new("pair?", Arity::Exact(1), is_pair),
+    Builtin::new("list?", Arity::Exact(1), is_list),
+    Builtin::new("cons", Arity::Exact(2), cons),
+    Builtin::new("set-car!", Arity::Exact(2), set_car),
+    Builtin::new("set-cdr!", Arity::Exact(2), set_cdr),
+    Builtin::new("eval", Arity::Exact(1), eval),
+    Builtin::new("error", Arity::AtLeast(1), error),
+    Builtin::new(
+        "procedure-arity",
+        Arity::Exact(1),
+        procedure_arity,
+    ),
+    Builtin::new(
+        "string->symbol",
+        Arity::Exact(1),
+        string_to_symbol,
+    ),
+    Builtin::new(
+        "symbol->string",
+        Arity::Exact(1),
+        symbol_to_string,
+    ),
+    Builtin::new("keyword?", Arity::Exact(1), is_keyword),
+    Builtin::new("make-hash", Arity::Range(0, 1), make_hash),
+    Builtin::new("hash?", Arity::Exact(1), is_hash),
+    Builtin::new("hash-ref", Arity::Range(2, 3), hash_ref),
+    Builtin::new("hash-set!", Arity::Exact(3), hash_set),
+    Builtin::new("hash-remove!", Arity::Exact(2), hash_remove),
+    Builtin::new("hash-has-key?", Arity::Exact(2), hash_has_key),
+    Builtin::new("hash-count", Arity::Exact(1), hash_count),
+    Builtin::new("hash-keys", Arity::Exact(1), hash_keys),
+    Builtin::new("hash-values", Arity::Exact(1), hash_values),
+    Builtin::new("hash->list", Arity::Exact(1), hash_to_list),
+    Builtin::new(
+        "hash-for-each",
+        Arity::Exact(2),
+        hash_for_each,
+    ),
+    Builtin::new("hash-map", Arity::Exact(2), hash_map),
+    Builtin::new("vector", Arity::AtLeast(0), vector),
+    Builtin::new("make-vector", Arity::Range(1, 2), make_vector),
+    Builtin::new("vector?", Arity::Exact(1), is_vector),
+    Builtin::new(
+        "vector-length",
+        Arity::Exact(1),
+        vector_length,
+    ),
+    Builtin::new("vector-ref", Arity::Exact(2), vector_ref),
+    Builtin::new("vector-set!", Arity::Exact(3), vector_set),
+    Builtin::new("vector-fill!", Arity::Exact(2), vector_fill),
+    Builtin::new("vector-copy", Arity::Range(1, 3), vector_copy),
+    Builtin::new(
+        "vector->list",
+        Arity::Exact(1),
+        vector_to_list,
+    ),
+    Builtin::new(
+        "list->vector",
+        Arity::Exact(1),
+        list_to_vector,
+    ),
+    Builtin::new("vector-map", Arity::Exact(2), vector_map),
+    Builtin::new("box", Arity::Exact(1), make_box),
+    Builtin::new("box?", Arity::Exact(1), is_box),
+    Builtin::new("unbox", Arity::Exact(1), unbox),
+    Builtin::new("set-box!", Arity::Exact(2), set_box),
+    Builtin::new("+", Arity::AtLeast(0), add),
+    Builtin::new("-", Arity::AtLeast(1), sub),
+    Builtin::new("*", Arity::AtLeast(0), mul),
+    Builtin::new("/", Arity::AtLeast(1), div),
+    Builtin::new("%", Arity::Exact(2), rem),
+    Builtin::new("quotient", Arity::Exact(2), quotient),
+    Builtin::new("remainder", Arity::Exact(2), remainder),
+    Builtin::new("modulo", Arity::Exact(2), modulo),
+    Builtin::new("numerator", Arity::Exact(1), numerator),
+    Builtin::new("denominator", Arity::Exact(1), denominator),
+    Builtin::new(
+        "exact->inexact",
+        Arity::Exact(1),
+        exact_to_inexact,
+    ),
+    Builtin::new(
+        "inexact->exact",
+        Arity::Exact(1),
+        inexact_to_exact,
+    ),
+    Builtin::new("=", Arity::AtLeast(1), eq),
+    Builtin::new("<", Arity::AtLeast(1), lt),
+    Builtin::new(">", Arity::AtLeast(1), gt),
+];
+
+pub fn register(env: &mut Env) {
+    for builtin in BUILTINS {
+        env.set(
+            Symbol::intern(builtin.name),
+            Object::Builtin(*builtin),
+        );
+    }
+}
+
+//  在调用处的 env 中把数据当作代码求值
+fn eval(
+    args: &[Object],
+    env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    eval_obj(&args[0], env.clone())
+}
+
+//  (error "message" irritant ...)，抛出 User 错误
+fn error(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let message = args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Err(EvalErrorKind::User(message).into())
+}
+
+//  (procedure-arity f) 返回 (min . max)，参数个数没有上限时 max 为 false
+fn procedure_arity(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let arity = match &args[0] {
+        Object::Builtin(builtin) => builtin.arity,
+        Object::Lambda(procedure) => procedure.params.arity(),
+        other => {
+            return Err(EvalError::type_mismatch(
+                "procedure",
+                other,
+            ))
+        }
+    };
+    let (min, max) = match arity {
+        Arity::Exact(n) => (n, Some(n)),
+        Arity::AtLeast(n) => (n, None),
+        Arity::Range(min, max) => (min, Some(max)),
+    };
+    let bound = |n: usize| Object::Integer(n as i64);
+    Ok(Object::cons(
+        bound(min),
+        max.map_or(Object::Bool(false), bound),
+    ))
+}
+
+fn binary_op(
+    operation: &str,
+    left: &Object,
+    right: &Object,
+) -> Result<Object, EvalError> {
+    if let (Some(l), Some(r)) =
+        (Number::from_object(left), Number::from_object(right))
+    {
+        let integer_op: Option<
+            fn(&Number, &Number) -> Option<Number>,
+        > = match operation {
+            "remainder" => Some(number::remainder),
+            "quotient" => Some(number::quotient),
+            "modulo" => Some(number::modulo),
+            _ => None,
+        };
+        if let Some(integer_op) = integer_op {
+            if !l.is_integer() {
+                return Err(EvalError::type_mismatch(
+                    "integer", left,
+                ));
+            }
+            if !r.is_integer() {
+                return Err(EvalError::type_mismatch(
+                    "integer", right,
+                ));
+            }
+            let result = integer_op(&l, &r)
+                .ok_or(EvalErrorKind::DivisionByZero)?;
+            return Ok(result.into_object());
+        }
+
+        let result = match operation {
+            "+" => number::add(&l, &r),
+            "-" => number::sub(&l, &r),
+            "*" => number::mul(&l, &r),
+            "/" => number::div(&l, &r)
+                .ok_or(EvalErrorKind::DivisionByZero)?,
+            "%" => number::remainder(&l, &r)
+                .ok_or(EvalErrorKind::DivisionByZero)?,
+            _ => {
+                let ordering = number::compare(&l, &r);
+                return compare_op(operation, ordering);
+            }
+        };
+        return Ok(result.into_object());
+    }
+
+    let ordering = match (operation, left, right) {
+        ("+", Object::String(l), Object::String(r)) => {
+            return Ok(Object::String(format!("{}{}", l, r)))
+        }
+        (
+            "=" | "<" | ">",
+            Object::String(l),
+            Object::String(r),
+        ) => l.partial_cmp(r),
+        ("=" | "<" | ">", Object::Char(l), Object::Char(r)) => {
+            l.partial_cmp(r)
+        }
+        //  其余的值按结构比较：symbol、关键字、list、向量等，类型不同时不相等
+        ("=", _, _) => return Ok(Object::Bool(left == right)),
+        //  报告第一个不是数字的参数
+        _ => {
+            let got = match Number::from_object(left) {
+                Some(_) => right,
+                None => left,
+            };
+            return Err(EvalError::type_mismatch("number", got));
+        }
+    };
+    compare_op(operation, ordering)
+}
+
+//  从左到右折叠，(+) 和 (*) 返回单位元，(- x) 和 (/ x) 相当于 (- 0 x) 和 (/ 1 x)
+fn fold_op(
+    operation: &str,
+    unit: i64,
+    args: &[Object],
+) -> Result<Object, EvalError> {
+    let unit = Object::Integer(unit);
+    match args {
+        [] => Ok(unit),
+        [arg] => binary_op(operation, &unit, arg),
+        [first, rest @ ..] => {
+            rest.iter().try_fold(first.clone(), |acc, arg| {
+                binary_op(operation, &acc, arg)
+            })
+        }
+    }
+}
+
+//  (< a b c) 要求每一对相邻的参数都满足，所有参数都会做类型检查
+fn chain_op(
+    operation: &str,
+    args: &[Object],
+) -> Result<Object, EvalError> {
+    if let [arg] = args {
+        binary_op(operation, arg, arg)?;
+        return Ok(Object::Bool(true));
+    }
+    let mut result = true;
+    for pair in args.windows(2) {
+        if binary_op(operation, &pair[0], &pair[1])?
+            == Object::Bool(false)
+        {
+            result = false;
+        }
+    }
+    Ok(Object::Bool(result))
+}
+
+fn compare_op(
+    operation: &str,
+    ordering: Option<Ordering>,
+) -> Result<Object, EvalError> {
+    let result = match operation {
+        "=" => ordering == Some(Ordering::Equal),
+        "<" => ordering == Some(Ordering::Less),
+        ">" => ordering == Some(Ordering::Greater),
+        _ => {
+            return Err(EvalErrorKind::Runtime(format!(
+                "Unknown operator {}",
+                operation
+            ))
+            .into())
+        }
+    };
+    Ok(Object::Bool(result))
+}
+
+fn add(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    fold_op("+", 0, args)
+}
+
+fn sub(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    fold_op("-", 0, args)
+}
+
+fn mul(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    fold_op("*", 1, args)
+}
+
+fn div(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    fold_op("/", 1, args)
+}
+
+fn rem(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    binary_op("%", &args[0], &args[1])
+}
+
+fn quotient(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    binary_op("quotient", &args[0], &args[1])
+}
+
+fn remainder(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    binary_op("remainder", &args[0], &args[1])
+}
+
+fn modulo(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    binary_op("modulo", &args[0], &args[1])
+}
+
+//  只接受数字的单参数运算，f 返回 None 时参数没有对应的结果，如 NaN
+fn number_op(
+    args: &[Object],
+    f: fn(&Number) -> Option<Number>,
+) -> Result<Object, EvalError> {
+    let n = Number::from_object(&args[0]).ok_or_else(|| {
+        EvalError::type_mismatch("number", &args[0])
+    })?;
+    f(&n).map(Number::into_object).ok_or_else(|| {
+        EvalError::type_mismatch("finite number", &args[0])
+    })
+}
+
+fn numerator(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    number_op(args, Number::numerator)
+}
+
+fn denominator(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    number_op(args, Number::denominator)
+}
+
+fn exact_to_inexact(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    number_op(args, |n| Some(n.to_inexact()))
+}
+
+fn inexact_to_exact(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    number_op(args, Number::to_exact)
+}
+
+fn eq(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    chain_op("=", args)
+}
+
+fn lt(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    chain_op("<", args)
+}
+
+fn gt(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    chain_op(">", args)
+}
+
+fn list(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::list(args.to_vec()))
+}
+
+fn car(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match &args[0] {
+        Object::Pair(pair) => Ok(pair.car()),
+        _ => Err(EvalError::type_mismatch("pair", &args[0])),
+    }
+}
+
+fn cdr(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match &args[0] {
+        Object::Pair(pair) => Ok(pair.cdr()),
+        _ => Err(EvalError::type_mismatch("pair", &args[0])),
+    }
+}
+
+fn length(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match args[0].to_vec() {
+        Some(list) => Ok(Object::Integer(list.len() as i64)),
+        None => Err(EvalError::type_mismatch("list", &args[0])),
+    }
+}
+
+fn is_null(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::Bool(args[0] == Object::Nil))
+}
+
+fn is_pair(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::Bool(matches!(args[0], Object::Pair(_))))
+}
+
+fn is_list(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::Bool(args[0].is_list()))
+}
+
+fn cons(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::cons(args[0].clone(), args[1].clone()))
+}
+
+//  修改后共享这个 pair 的 list 都能看到新值
+fn set_car(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match &args[0] {
+        Object::Pair(pair) => {
+            pair.set_car(args[1].clone());
+            Ok(Object::Void)
+        }
+        _ => Err(EvalError::type_mismatch("pair", &args[0])),
+    }
+}
+
+fn set_cdr(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match &args[0] {
+        Object::Pair(pair) => {
+            pair.set_cdr(args[1].clone());
+            Ok(Object::Void)
+        }
+        _ => Err(EvalError::type_mismatch("pair", &args[0])),
+    }
+}
+
+fn string_to_symbol(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match &args[0] {
+        Object::String(s) => {
+            Ok(Object::Symbol(Symbol::intern(s)))
+        }
+        _ => Err(EvalError::type_mismatch("string", &args[0])),
+    }
+}
+
+fn symbol_to_string(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match &args[0] {
+        Object::Symbol(sym) => {
+            Ok(Object::String(sym.as_str().to_string()))
+        }
+        _ => Err(EvalError::type_mismatch("symbol", &args[0])),
+    }
+}
+
+fn is_keyword(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::Bool(matches!(args[0], Object::Keyword(_))))
+}
+
+type HashTable = Rc<RefCell<IndexMap<Object, Object>>>;
+
+fn hash_table(obj: &Object) -> Result<&HashTable, EvalError> {
+    match obj {
+        Object::HashMap(map) => Ok(map),
+        _ => Err(EvalError::type_mismatch("hash map", obj)),
+    }
+}
+
+fn hash_key(key: &Object) -> Result<Object, EvalError> {
+    if !key.is_hashable() {
+        return Err(EvalError::type_mismatch(
+            "hashable value",
+            key,
+        ));
+    }
+    Ok(key.clone())
+}
+
+//  (make-hash) 或 (make-hash '((k . v) ...))
+fn make_hash(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let mut map = IndexMap::new();
+    if let Some(alist) = args.first() {
+        let entries = alist.to_vec().ok_or_else(|| {
+            EvalError::type_mismatch("association list", alist)
+        })?;
+        for entry in entries {
+            match &entry {
+                Object::Pair(pair) => {
+                    let key = hash_key(&pair.car())?;
+                    map.insert(key, pair.cdr());
+                }
+                _ => {
+                    return Err(EvalError::type_mismatch(
+                        "pair", &entry,
+                    ))
+                }
+            }
+        }
+    }
+    Ok(Object::HashMap(Rc::new(RefCell::new(map))))
+}
+
+fn is_hash(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::Bool(matches!(args[0], Object::HashMap(_))))
+}
+
+//  key 不存在时返回第三个参数，没有第三个参数则报错
+fn hash_ref(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let map = hash_table(&args[0])?;
+    let key = hash_key(&args[1])?;
+    let value = map.borrow().get(&key).cloned();
+    match (value, args.get(2)) {
+        (Some(value), _) => Ok(value),
+        (None, Some(default)) => Ok(default.clone()),
+        (None, None) => {
+            Err(EvalErrorKind::KeyNotFound(key).into())
+        }
+    }
+}
+
+fn hash_set(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let map = hash_table(&args[0])?;
+    let key = hash_key(&args[1])?;
+    map.borrow_mut().insert(key, args[2].clone());
+    Ok(Object::Void)
+}
+
+fn hash_remove(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let map = hash_table(&args[0])?;
+    let key = hash_key(&args[1])?;
+    map.borrow_mut().shift_remove(&key);
+    Ok(Object::Void)
+}
+
+fn hash_has_key(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let map = hash_table(&args[0])?;
+    let key = hash_key(&args[1])?;
+    let found = map.borrow().contains_key(&key);
+    Ok(Object::Bool(found))
+}
+
+fn hash_count(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let map = hash_table(&args[0])?;
+    let count = map.borrow().len();
+    Ok(Object::Integer(count as i64))
+}
+
+fn hash_keys(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let map = hash_table(&args[0])?;
+    let keys = map.borrow().keys().cloned().collect();
+    Ok(Object::list(keys))
+}
+
+fn hash_values(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let map = hash_table(&args[0])?;
+    let values = map.borrow().values().cloned().collect();
+    Ok(Object::list(values))
+}
+
+//  ((k . v) ...)
+fn hash_to_list(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let map = hash_table(&args[0])?;
+    let entries = map
+        .borrow()
+        .iter()
+        .map(|(key, value)| {
+            Object::cons(key.clone(), value.clone())
+        })
+        .collect();
+    Ok(Object::list(entries))
+}
+
+//  先复制出所有条目，过程中可以修改哈希表本身
+fn hash_entries(
+    obj: &Object,
+) -> Result<Vec<(Object, Object)>, EvalError> {
+    let map = hash_table(obj)?;
+    let entries = map
+        .borrow()
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    Ok(entries)
+}
+
+//  (hash-for-each map (lambda (k v) ...))
+fn hash_for_each(
+    args: &[Object],
+    env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    for (key, value) in hash_entries(&args[0])? {
+        apply(&args[1], vec![key, value], env)?;
+    }
+    Ok(Object::Void)
+}
+
+//  (hash-map map (lambda (k v) ...))，返回每次调用结果组成的 list
+fn hash_map(
+    args: &[Object],
+    env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let mut results = vec![];
+    for (key, value) in hash_entries(&args[0])? {
+        results.push(apply(&args[1], vec![key, value], env)?);
+    }
+    Ok(Object::list(results))
+}
+
+type Vector = Rc<RefCell<Vec<Object>>>;
+
+fn vector_items(obj: &Object) -> Result<&Vector, EvalError> {
+    match obj {
+        Object::Vector(items) => Ok(items),
+        _ => Err(EvalError::type_mismatch("vector", obj)),
+    }
+}
+
+//  不超过 limit 的非负整数，len 是向量的长度，用于报错
+fn vector_bound(
+    obj: &Object,
+    limit: usize,
+    len: usize,
+) -> Result<usize, EvalError> {
+    let index = match obj {
+        Object::Integer(n) => *n,
+        _ => {
+            return Err(EvalError::type_mismatch("integer", obj))
+        }
+    };
+    match usize::try_from(index) {
+        Ok(n) if n <= limit => Ok(n),
+        _ => {
+            Err(EvalErrorKind::IndexOutOfRange { index, len }
+                .into())
+        }
+    }
+}
+
+//  可以读写的下标，必须小于向量长度
+fn vector_index(
+    obj: &Object,
+    len: usize,
+) -> Result<usize, EvalError> {
+    match vector_bound(obj, len, len)? {
+        index if index < len => Ok(index),
+        index => Err(EvalErrorKind::IndexOutOfRange {
+            index: index as i64,
+            len,
+        }
+        .into()),
+    }
+}
+
+fn vector(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::vector(args.to_vec()))
+}
+
+//  (make-vector n) 或 (make-vector n fill)，默认用 0 填充
+fn make_vector(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let len = match &args[0] {
+        Object::Integer(n) if *n >= 0 => *n as usize,
+        obj => {
+            return Err(EvalError::type_mismatch(
+                "non-negative integer",
+                obj,
+            ))
+        }
+    };
+    let fill =
+        args.get(1).cloned().unwrap_or(Object::Integer(0));
+    //  长度过大时分配失败，报错而不是让进程退出
+    let mut items = vec![];
+    if items.try_reserve_exact(len).is_err() {
+        return Err(EvalErrorKind::Runtime(format!(
+            "Cannot allocate vector of length {}",
+            len
+        ))
+        .into());
+    }
+    items.resize(len, fill);
+    Ok(Object::vector(items))
+}
+
+fn is_vector(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::Bool(matches!(args[0], Object::Vector(_))))
+}
+
+fn vector_length(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let items = vector_items(&args[0])?;
+    let len = items.borrow().len();
+    Ok(Object::Integer(len as i64))
+}
+
+fn vector_ref(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let items = vector_items(&args[0])?.borrow();
+    let i = vector_index(&args[1], items.len())?;
+    Ok(items[i].clone())
+}
+
+fn vector_set(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let mut items = vector_items(&args[0])?.borrow_mut();
+    let i = vector_index(&args[1], items.len())?;
+    items[i] = args[2].clone();
+    Ok(Object::Void)
+}
+
+fn vector_fill(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let items = vector_items(&args[0])?;
+    items.borrow_mut().fill(args[1].clone());
+    Ok(Object::Void)
+}
+
+//  (vector-copy v)、(vector-copy v start) 或 (vector-copy v start end)
+//  复制 [start, end) 之间的元素，得到新的向量
+fn vector_copy(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let items = vector_items(&args[0])?.borrow();
+    let len = items.len();
+    let end = match args.get(2) {
+        Some(end) => vector_bound(end, len, len)?,
+        None => len,
+    };
+    //  start 不能超过 end
+    let start = match args.get(1) {
+        Some(start) => vector_bound(start, end, len)?,
+        None => 0,
+    };
+    Ok(Object::vector(items[start..end].to_vec()))
+}
+
+fn vector_to_list(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let items = vector_items(&args[0])?;
+    Ok(Object::list(items.borrow().clone()))
+}
+
+fn list_to_vector(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let items = args[0].to_vec().ok_or_else(|| {
+        EvalError::type_mismatch("list", &args[0])
+    })?;
+    Ok(Object::vector(items))
+}
+
+//  (vector-map proc v)，先复制出元素，过程中可以修改向量本身
+fn vector_map(
+    args: &[Object],
+    env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let items = vector_items(&args[1])?;
+    let items = items.borrow().clone();
+    let mut results = Vec::with_capacity(items.len());
+    for item in items {
+        results.push(apply(&args[0], vec![item], env)?);
+    }
+    Ok(Object::vector(results))
+}
+
+fn make_box(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::Box(Rc::new(RefCell::new(args[0].clone()))))
+}
+
+fn is_box(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    Ok(Object::Bool(matches!(args[0], Object::Box(_))))
+}
+
+fn unbox(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match &args[0] {
+        Object::Box(value) => Ok(value.borrow().clone()),
+        _ => Err(EvalError::type_mismatch("box", &args[0])),
+    }
+}
+
+fn set_box(
+    args: &[Object],
+    _env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match &args[0] {
+        Object::Box(value) => {
+            *value.borrow_mut() = args[1].clone();
+            Ok(Object::Void)
+        }
+        _ => Err(EvalError::type_mismatch("box", &args[0])),
+    }
+}
@@ -1,16 +1,22 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
+use crate::builtins;
+use crate::eval::{EvalError, EvalErrorKind};
 use crate::object::*;
+use crate::symbol::Symbol;
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default)]
 pub struct Env {
     parent: Option<Rc<RefCell<Env>>>,
-    vars: HashMap<String, Object>,
+    vars: HashMap<Symbol, Object>,
 }
 
 impl Env {
+    //  全局 env，预先绑定所有内置函数
     pub fn new() -> Self {
-        Default::default()
+        let mut env: Env = Default::default();
+        builtins::register(&mut env);
+        env
     }
 
     pub fn extend(parent: Rc<RefCell<Env>>) -> Self {
@@ -20,12 +26,32 @@ impl Env {
         }
     }
 
-    pub fn set(&mut self, name: String, value: Object) {
+    pub fn set(&mut self, name: Symbol, value: Object) {
         self.vars.insert(name, value);
     }
 
-    pub fn get(&self, name: &str) -> Option<Object> {
-        match self.vars.get(name) {
+    //  修改最近一层 env 中已有的绑定，不会新建绑定
+    pub fn update(
+        &mut self,
+        name: Symbol,
+        value: Object,
+    ) -> Result<(), EvalError> {
+        if let Some(slot) = self.vars.get_mut(&name) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.parent {
+            Some(parent) => {
+                parent.borrow_mut().update(name, value)
+            }
+            None => {
+                Err(EvalErrorKind::UnboundVariable(name).into())
+            }
+        }
+    }
+
+    pub fn get(&self, name: Symbol) -> Option<Object> {
+        match self.vars.get(&name) {
             Some(value) => Some(value.clone()),
             None => self
                 .parent
@@ -1,683 +1,1325 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt;
 use std::rc::Rc;
 
+use indexmap::IndexMap;
+
 use crate::env::*;
 use crate::object::*;
 use crate::parser::*;
+use crate::span::Span;
+use crate::stack;
+use crate::symbol::Symbol;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    //  装箱后 Result<Object, EvalError> 不会因为错误信息而变大
+    kind: Box<EvalErrorKind>,
+    span: Option<Span>,
+    //  出错时正在执行的过程，最内层在前；尾调用不保留调用者的 frame
+    frames: Vec<Frame>,
+}
 
-fn eval_binary_op(
-    operation: &str,
-    list: &[Object],
-    env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    if list.len() != 2 {
-        return Err(format!(
-            "Invalid number of arguments for binary operation"
-        ));
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalErrorKind {
+    //  引用或 set! 了未绑定的变量
+    UnboundVariable(Symbol),
+    //  参数或条件的类型不对，expected 描述需要的类型
+    TypeMismatch {
+        expected: &'static str,
+        got: Object,
+    },
+    //  过程或特殊形式收到的参数个数不对
+    Arity {
+        name: String,
+        expected: Arity,
+        got: usize,
+    },
+    //  精确数除以 0
+    DivisionByZero,
+    //  向量下标越界，或者不是合法的下标
+    IndexOutOfRange {
+        index: i64,
+        len: usize,
+    },
+    //  哈希表中没有这个 key
+    KeyNotFound(Object),
+    //  调用的值不是过程
+    NotAProcedure(Object),
+    //  调用时传入了过程没有声明的关键字参数
+    UnknownKeyword {
+        name: String,
+        keyword: Symbol,
+    },
+    //  同一个关键字参数传入了两次
+    DuplicateKeyword {
+        name: String,
+        keyword: Symbol,
+    },
+    //  关键字参数后面缺少值，如 (f :port)
+    MissingKeywordValue {
+        name: String,
+        keyword: Symbol,
+    },
+    //  特殊形式的写法不对，如 (define 1 2)、(+ . 1)
+    InvalidSyntax(String),
+    //  eval 的输入无法读入
+    Parse(ParseError),
+    //  程序通过 error 主动抛出的错误
+    User(String),
+    //  其他运行时错误，如 cond 没有匹配的分支
+    Runtime(String),
+}
+
+//  Lisp 调用栈中的一层：过程名和调用处的位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    pub span: Option<Span>,
+}
+
+impl EvalError {
+    pub fn new(kind: EvalErrorKind) -> Self {
+        EvalError {
+            kind: Box::new(kind),
+            span: None,
+            frames: vec![],
+        }
     }
 
-    let left = eval_obj(&list[0], env.clone()).unwrap();
-    let right = eval_obj(&list[1], env.clone()).unwrap();
+    pub(crate) fn type_mismatch(
+        expected: &'static str,
+        got: &Object,
+    ) -> Self {
+        EvalError::new(EvalErrorKind::TypeMismatch {
+            expected,
+            got: got.clone(),
+        })
+    }
 
-    match operation {
-        "+" => match (&left, &right) {
-            (Object::Integer(l), Object::Integer(r)) => {
-                Ok(Object::Integer(l + r))
-            }
-            (Object::Float(l), Object::Float(r)) => {
-                Ok(Object::Float(l + r))
-            }
-            (Object::Integer(l), Object::Float(r)) => {
-                Ok(Object::Float(*l as f64 + r))
-            }
-            (Object::Float(l), Object::Integer(r)) => {
-                Ok(Object::Float(l + *r as f64))
-            }
-            (Object::String(l), Object::String(r)) => {
-                Ok(Object::String(format!("{}{}", l, r)))
-            }
-            _ => Err(format!(
-                "Invalid types for + operator {} {}",
-                left, right
-            )),
-        },
-        "-" => match (&left, &right) {
-            (Object::Integer(l), Object::Integer(r)) => {
-                Ok(Object::Integer(l - r))
-            }
-            (Object::Float(l), Object::Float(r)) => {
-                Ok(Object::Float(l - r))
-            }
-            (Object::Integer(l), Object::Float(r)) => {
-                Ok(Object::Float(*l as f64 - r))
-            }
-            (Object::Float(l), Object::Integer(r)) => {
-                Ok(Object::Float(l - *r as f64))
-            }
-            _ => Err(format!(
-                "Invalid types for - operator {} {}",
-                left, right
-            )),
-        },
-        "*" => match (&left, &right) {
-            (Object::Integer(l), Object::Integer(r)) => {
-                return Ok(Object::Integer(l * r))
-            }
-            (Object::Float(l), Object::Float(r)) => {
-                return Ok(Object::Float(l * r))
-            }
-            (Object::Integer(l), Object::Float(r)) => {
-                return Ok(Object::Float(*l as f64 * r))
-            }
-            (Object::Float(l), Object::Integer(r)) => {
-                return Ok(Object::Float(l * *r as f64))
-            }
-            _ => Err(format!(
-                "Invalid types for * operator {} {}",
-                left, right
-            )),
-        },
-        "/" => match (&left, &right) {
-            (Object::Integer(l), Object::Integer(r)) => {
-                return Ok(Object::Integer(l / r))
-            }
-            (Object::Float(l), Object::Float(r)) => {
-                return Ok(Object::Float(l / r))
-            }
-            (Object::Integer(l), Object::Float(r)) => {
-                return Ok(Object::Float(*l as f64 / r))
-            }
-            (Object::Float(l), Object::Integer(r)) => {
-                return Ok(Object::Float(l / *r as f64))
-            }
-            _ => Err(format!(
-                "Invalid types for / operator {} {}",
-                left, right
-            )),
-        },
-        "%" => match (&left, &right) {
-            (Object::Integer(l), Object::Integer(r)) => {
-                return Ok(Object::Integer(l % r))
+    pub fn kind(&self) -> &EvalErrorKind {
+        &self.kind
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    //  只记录最内层的位置，外层 list 不覆盖
+    fn with_span(mut self, span: &Option<Span>) -> Self {
+        if self.span.is_none() {
+            self.span = span.clone();
+        }
+        self
+    }
+
+    fn with_frame(mut self, frame: Option<Frame>) -> Self {
+        self.frames.extend(frame);
+        self
+    }
+}
+
+impl From<EvalErrorKind> for EvalError {
+    fn from(kind: EvalErrorKind) -> Self {
+        EvalError::new(kind)
+    }
+}
+
+impl From<ParseError> for EvalError {
+    fn from(err: ParseError) -> Self {
+        EvalErrorKind::Parse(err).into()
+    }
+}
+
+impl fmt::Display for EvalErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalErrorKind::UnboundVariable(name) => {
+                write!(f, "Undefined symbol {}", name)
             }
-            (Object::Float(l), Object::Float(r)) => {
-                return Ok(Object::Float(l % r))
+            EvalErrorKind::TypeMismatch { expected, got } => {
+                write!(
+                    f,
+                    "Invalid type: expected {}, got {}",
+                    expected, got
+                )
             }
-            (Object::Integer(l), Object::Float(r)) => {
-                return Ok(Object::Float(*l as f64 % r))
+            EvalErrorKind::Arity {
+                name,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Invalid number of arguments for {}: expected {}, got {}",
+                name, expected, got
+            ),
+            EvalErrorKind::DivisionByZero => {
+                write!(f, "Division by zero")
             }
-            (Object::Float(l), Object::Integer(r)) => {
-                return Ok(Object::Float(l % *r as f64))
+            EvalErrorKind::IndexOutOfRange { index, len } => {
+                write!(
+                    f,
+                    "Index {} out of range for vector of length {}",
+                    index, len
+                )
             }
-            _ => Err(format!(
-                "Invalid types for % operator {} {}",
-                left, right
-            )),
-        },
-        "=" => match (&left, &right) {
-            (Object::Integer(l), Object::Integer(r)) => {
-                return Ok(Object::Bool(l == r))
+            EvalErrorKind::KeyNotFound(key) => {
+                write!(f, "Key {} not found in hash map", key)
             }
-            (Object::String(l), Object::String(r)) => {
-                return Ok(Object::Bool(l == r))
+            EvalErrorKind::NotAProcedure(obj) => {
+                write!(f, "Not a procedure {}", obj)
             }
-            _ => Err(format!(
-                "Invalid types for = operator {} {}",
-                left, right
-            )),
-        },
-        ">" => match (&left, &right) {
-            (Object::Integer(l), Object::Integer(r)) => {
-                return Ok(Object::Bool(l > r))
+            EvalErrorKind::UnknownKeyword {
+                name,
+                keyword,
+            } => write!(
+                f,
+                "Unknown keyword :{} for {}",
+                keyword, name
+            ),
+            EvalErrorKind::DuplicateKeyword {
+                name,
+                keyword,
+            } => write!(
+                f,
+                "Duplicate keyword :{} for {}",
+                keyword, name
+            ),
+            EvalErrorKind::MissingKeywordValue {
+                name,
+                keyword,
+            } => write!(
+                f,
+                "Missing value for keyword :{} for {}",
+                keyword, name
+            ),
+            EvalErrorKind::InvalidSyntax(err)
+            | EvalErrorKind::User(err)
+            | EvalErrorKind::Runtime(err) => write!(f, "{}", err),
+            EvalErrorKind::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+//  调用栈中连续相同的 frame 合并为一行，合并后仍然超过两倍时只显示两端各 FRAMES_SHOWN 行
+const FRAMES_SHOWN: usize = 10;
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(span) = &self.span {
+            write!(f, " at {}\n{}", span, span.snippet())?;
+        }
+
+        let mut runs: Vec<(&Frame, usize)> = vec![];
+        for frame in &self.frames {
+            match runs.last_mut() {
+                Some((last, count)) if *last == frame => {
+                    *count += 1
+                }
+                _ => runs.push((frame, 1)),
             }
-            (Object::String(l), Object::String(r)) => {
-                return Ok(Object::Bool(l > r))
+        }
+        let hidden = runs.len().saturating_sub(2 * FRAMES_SHOWN);
+        for (i, (frame, count)) in runs.iter().enumerate() {
+            if hidden > 0
+                && FRAMES_SHOWN <= i
+                && i < runs.len() - FRAMES_SHOWN
+            {
+                if i == FRAMES_SHOWN {
+                    write!(f, "\n  ... {} more frames", hidden)?;
+                }
+                continue;
             }
-            _ => Err(format!(
-                "Invalid types for > operator {} {}",
-                left, right
-            )),
-        },
-        "<" => match (&left, &right) {
-            (Object::Integer(l), Object::Integer(r)) => {
-                return Ok(Object::Bool(l < r))
+            write!(f, "\n  in {}", frame.name)?;
+            if let Some(span) = &frame.span {
+                write!(f, " at {}", span)?;
             }
-            (Object::String(l), Object::String(r)) => {
-                return Ok(Object::Bool(l < r))
+            if *count > 1 {
+                write!(f, " (repeated {} times)", count)?;
             }
-            _ => Err(format!(
-                "Invalid types for < operator {} {}",
-                left, right
-            )),
-        },
-        _ => todo!(),
+        }
+        Ok(())
+    }
+}
+
+impl Error for EvalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind() {
+            EvalErrorKind::Parse(err) => Some(err),
+            _ => None,
+        }
     }
 }
 
+//  特殊形式的参数个数不对
+fn form_arity(
+    name: &str,
+    expected: usize,
+    got: usize,
+) -> EvalError {
+    EvalErrorKind::Arity {
+        name: name.to_string(),
+        expected: Arity::Exact(expected),
+        got,
+    }
+    .into()
+}
+
+fn invalid_syntax(what: &str, obj: &Object) -> EvalError {
+    EvalErrorKind::InvalidSyntax(format!(
+        "Invalid {} {}",
+        what, obj
+    ))
+    .into()
+}
+
 fn eval_begin(
     list: &[Object],
+    source: &Object,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    let mut result = Object::Void;
-
+) -> Result<Tail, EvalError> {
     let new_env = Rc::new(RefCell::new(Env::extend(env)));
+    eval_sequence(list, source, new_env)
+}
 
-    for obj in list {
-        result = eval_obj(obj, new_env.clone())?;
+//  依次求值除最后一个以外的表达式，最后一个处于尾位置
+fn eval_sequence(
+    list: &[Object],
+    source: &Object,
+    env: Rc<RefCell<Env>>,
+) -> Result<Tail, EvalError> {
+    let last = match list.len() {
+        0 => return Ok(Tail::Return(Object::Void)),
+        len => len - 1,
+    };
+    for index in 0..last {
+        eval_item(list, source, index, env.clone())?;
     }
-    Ok(result)
+    Ok(tail_item(list, source, last, env))
 }
 
-fn eval_define(
+fn eval_if(
     list: &[Object],
+    source: &Object,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    if list.len() != 2 {
-        return Err(format!(
-            "Invalid number of arguments for define"
-        ));
+) -> Result<Tail, EvalError> {
+    //  (if test then [else])，没有 else 时条件为假返回 Void
+    if !(2..=3).contains(&list.len()) {
+        return Err(EvalErrorKind::Arity {
+            name: "if".to_string(),
+            expected: Arity::Range(2, 3),
+            got: list.len(),
+        }
+        .into());
+    }
+
+    if eval_item(list, source, 0, env.clone())?.is_truthy() {
+        Ok(tail_item(list, source, 1, env))
+    } else if list.len() == 3 {
+        Ok(tail_item(list, source, 2, env))
+    } else {
+        Ok(Tail::Return(Object::Void))
+    }
+}
+
+//  (and a b ...) 和 (or a b ...)，真假和 if 的规则相同，返回决定结果的那个值
+//  最后一个表达式处于尾位置
+fn eval_and_or(
+    head: &str,
+    list: &[Object],
+    source: &Object,
+    env: Rc<RefCell<Env>>,
+) -> Result<Tail, EvalError> {
+    let is_and = head == "and";
+    let last = match list.len() {
+        0 => return Ok(Tail::Return(Object::Bool(is_and))),
+        len => len - 1,
+    };
+    for index in 0..last {
+        let value = eval_item(list, source, index, env.clone())?;
+        if value.is_truthy() != is_and {
+            return Ok(Tail::Return(value));
+        }
     }
+    Ok(tail_item(list, source, last, env))
+}
 
-    let (name, value) = match &list[0] {
-        Object::Symbol(name) => {
-            (name.clone(), eval_obj(&list[1], env.clone())?)
+fn eval_define(
+    list: &[Object],
+    source: &Object,
+    env: Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    //  (define name value) 或者 (define (f params) body ...)
+    let (name, value) = match list.first() {
+        Some(Object::Symbol(name)) if list.len() == 2 => {
+            (*name, eval_item(list, source, 1, env.clone())?)
         }
-        Object::List(l) => {
-            let name = match &l[0] {
-                Object::Symbol(name) => name.clone(),
-                _ => return Err(format!("Invalid define")),
+        Some(Object::Symbol(_)) | None => {
+            return Err(form_arity("define", 2, list.len()))
+        }
+        Some(Object::Pair(pair)) if list.len() >= 2 => {
+            let name = match pair.car() {
+                Object::Symbol(name) => name,
+                _ => {
+                    return Err(invalid_syntax(
+                        "define", &list[0],
+                    ))
+                }
             };
 
-            let params = Object::List(l[1..].to_vec());
-            let body = list[1].clone();
+            //  (define (f params) body ...) 等价于 (define f (lambda (params) body ...))
+            let mut lambda = vec![pair.cdr()];
+            lambda.extend_from_slice(&list[1..]);
             let value =
-                eval_lambda(&[params, body], env.clone())?;
+                eval_lambda(&lambda, source, env.clone())?;
 
             (name, value)
         }
-        _ => return Err(format!("Invalid define")),
+        _ => return Err(invalid_syntax("define", &list[0])),
     };
 
+    //  (define g f) 不会改变 f 已有的名字
+    if let Object::Lambda(procedure) = &value {
+        if procedure.name.get().is_none() {
+            procedure.name.set(Some(name));
+        }
+    }
     env.borrow_mut().set(name, value);
     Ok(Object::Void)
 }
 
-fn eval_lambda(
+//  (set! name value)，name 必须已经绑定
+fn eval_set(
     list: &[Object],
+    source: &Object,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
+) -> Result<Object, EvalError> {
     if list.len() != 2 {
-        return Err(format!(
-            "Invalid number of arguments for lambda"
-        ));
+        return Err(form_arity("set!", 2, list.len()));
     }
 
-    let params = match &list[0] {
-        Object::List(list) => {
-            let mut params = vec![];
-            for param in list {
-                match param {
-                    Object::Symbol(param) => {
-                        params.push(param.clone())
-                    }
-                    _ => {
-                        return Err(format!(
-                            "Invalid lambda parameter {:?}",
-                            param
-                        ))
-                    }
-                }
-            }
-            params
+    let name = match &list[0] {
+        Object::Symbol(name) => *name,
+        _ => {
+            return Err(invalid_syntax("set! target", &list[0]))
         }
-        _ => return Err(format!("Invalid lambda")),
-    };
-
-    let body = match &list[1] {
-        Object::List(l) => l.clone(),
-        _ => return Err(format!("Invalid lambda")),
     };
-
-    Ok(Object::Lambda(params, body, env.clone()))
+    let value = eval_item(list, source, 1, env.clone())?;
+    env.borrow_mut().update(name, value)?;
+    Ok(Object::Void)
 }
 
-fn eval_list(
+fn eval_lambda(
     list: &[Object],
+    source: &Object,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    let mut list_data = vec![];
+) -> Result<Object, EvalError> {
+    //  (lambda params body ...)，body 至少有一个表达式
+    if list.len() < 2 {
+        return Err(EvalErrorKind::Arity {
+            name: "lambda".to_string(),
+            expected: Arity::AtLeast(2),
+            got: list.len(),
+        }
+        .into());
+    }
 
-    for obj in list {
-        list_data.push(eval_obj(obj, env.clone())?);
+    let params = parse_params(&list[0])?;
+    Ok(Object::Lambda(Rc::new(Procedure {
+        name: Cell::new(None),
+        params,
+        body: list[1..].to_vec(),
+        body_source: rest_source(source),
+        env: env.clone(),
+    })))
+}
+
+//  去掉第一项的 source，对应 list[1..]
+fn rest_source(source: &Object) -> Object {
+    match source {
+        Object::Pair(pair) => pair.cdr(),
+        _ => Object::Nil,
     }
-    Ok(Object::ListData(list_data))
 }
 
-fn eval_car(
+//  atom 没有记录位置，求值 list 的第 index 项出错时，使用 source 中对应的位置
+//  source 是 list 在源码中对应的那串 pair
+fn eval_item(
     list: &[Object],
+    source: &Object,
+    index: usize,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    if list.len() != 1 {
-        return Err(format!(
-            "Invalid number of arguments for car"
-        ));
-    }
-
-    let obj = eval_obj(&list[0], env.clone())?;
-
-    match obj {
-        Object::ListData(list) => Ok(list[0].clone()),
-        _ => {
-            Err(format!("Invalid type car argument {}", list[0]))
-        }
-    }
+) -> Result<Object, EvalError> {
+    eval_obj(&list[index], env)
+        .map_err(|err| err.with_span(&source.item_span(index)))
 }
 
-fn eval_cdr(
+//  处于尾位置的第 index 项，只有 symbol 求值会出错，其余的项不需要查找位置
+fn tail_item(
     list: &[Object],
+    source: &Object,
+    index: usize,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    if list.len() != 1 {
-        return Err(format!(
-            "Invalid number of arguments for cdr"
-        ));
-    }
+) -> Tail {
+    let obj = list[index].clone();
+    let span = match obj {
+        Object::Symbol(_) => source.item_span(index),
+        _ => None,
+    };
+    Tail::Eval(obj, span, env)
+}
 
-    let obj = eval_obj(&list[0], env.clone())?;
+//  参数列表当前所在的部分，由 #!optional 和 #!key 切换
+#[derive(Clone, Copy, PartialEq)]
+enum ParamSection {
+    Required,
+    Optional,
+    Key,
+}
 
-    match obj {
-        Object::ListData(list) => {
-            if list.len() >= 1 {
-                Ok(Object::ListData(list[1..].to_vec()))
-            } else {
-                Err(format!("Invalid number of list data"))
+//  (a b)、(a b . rest) 或者单独的 args，rest 收集剩余的参数
+//  (a (b 1))、(a #!optional b) 中 b 是可选参数，之后不能再出现必需参数
+//  (a #!key b (c 1)) 中 b、c 是关键字参数，不能和 rest 同时使用
+fn parse_params(obj: &Object) -> Result<Params, EvalError> {
+    let mut params = Params::default();
+    let mut section = ParamSection::Required;
+    let mut items = obj.iter();
+    for param in items.by_ref() {
+        let marker = match &param {
+            Object::Symbol(sym) => match sym.as_str() {
+                "#!optional" => Some(ParamSection::Optional),
+                "#!key" => Some(ParamSection::Key),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(marker) = marker {
+            if section == ParamSection::Key {
+                return Err(invalid_syntax(
+                    "lambda parameter",
+                    &param,
+                ));
             }
+            section = marker;
+            continue;
         }
-        _ => {
-            Err(format!("Invalid type cdr argument {}", list[0]))
-        }
-    }
-}
 
-fn eval_length(
-    list: &[Object],
-    env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    if list.len() != 1 {
-        return Err(format!(
-            "Invalid number of arguments for length"
-        ));
+        let (sym, default) =
+            param_spec(&param).ok_or_else(|| {
+                invalid_syntax("lambda parameter", &param)
+            })?;
+        match (section, default) {
+            (ParamSection::Key, default) => {
+                params.keys.push((sym, default))
+            }
+            (ParamSection::Required, None)
+                if params.optional.is_empty() =>
+            {
+                params.required.push(sym)
+            }
+            (ParamSection::Required, None) => {
+                return Err(EvalErrorKind::InvalidSyntax(format!(
+                    "Required parameter {} after optional parameters",
+                    sym
+                ))
+                .into())
+            }
+            (_, default) => params.optional.push((sym, default)),
+        }
     }
+    params.rest = match items.tail() {
+        Object::Nil => None,
+        Object::Symbol(sym) if section == ParamSection::Key => {
+            return Err(EvalErrorKind::InvalidSyntax(format!(
+                "Rest parameter {} cannot be used with #!key",
+                sym
+            ))
+            .into())
+        }
+        Object::Symbol(sym) => Some(*sym),
+        tail => {
+            return Err(invalid_syntax("lambda parameter", tail))
+        }
+    };
 
-    let obj = eval_obj(&list[0], env.clone())?;
-
-    match obj {
-        Object::ListData(list) => {
-            Ok(Object::Integer(list.len() as i64))
+    let mut names = params
+        .required
+        .iter()
+        .chain(params.optional.iter().map(|(name, _)| name))
+        .chain(params.keys.iter().map(|(name, _)| name))
+        .chain(&params.rest);
+    while let Some(name) = names.next() {
+        if names.clone().any(|other| other == name) {
+            return Err(EvalErrorKind::InvalidSyntax(format!(
+                "Duplicate lambda parameter {}",
+                name
+            ))
+            .into());
         }
-        _ => Err(format!(
-            "Invalid type length argument {}",
-            list[0]
-        )),
     }
+    Ok(params)
 }
 
-fn eval_is_null(
-    list: &[Object],
-    env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    if list.len() != 1 {
-        return Err(format!(
-            "Invalid number of arguments for is_null"
-        ));
-    }
-
-    let obj = eval_obj(&list[0], env.clone())?;
-
-    match obj {
-        Object::ListData(list) => {
-            if list.is_empty() {
-                Ok(Object::Bool(true))
-            } else {
-                Ok(Object::Bool(false))
+//  name 或者 (name default)
+fn param_spec(
+    param: &Object,
+) -> Option<(Symbol, Option<Object>)> {
+    match param {
+        Object::Symbol(sym) => Some((*sym, None)),
+        _ => match param.to_vec().as_deref() {
+            Some([Object::Symbol(sym), default]) => {
+                Some((*sym, Some(default.clone())))
             }
-        }
-        _ => Err(format!(
-            "Invalid type null? argument {}",
-            list[0]
-        )),
+            _ => None,
+        },
     }
 }
 
 fn eval_cond(
     list: &[Object],
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
+) -> Result<Tail, EvalError> {
+    //  (test body ...) 或者 (else body ...)，body 依次求值，最后一个处于尾位置
+    //  只有 (test) 时返回 test 的值
+    let else_symbol = Object::Symbol(Symbol::intern("else"));
     for obj in list {
-        match obj {
-            Object::List(list) => {
-                if list.len() != 2 {
-                    return Err(format!(
-                        "Invalid number of arguments for cond"
-                    ));
-                }
-
-                if list[0] == Object::Keyword("else".to_string())
-                {
-                    return eval_obj(&list[1], env.clone());
-                }
-                let cond_obj = eval_obj(&list[0], env.clone())?;
-                let cond = match cond_obj {
-                    Object::Bool(cond) => cond,
-                    _ => {
-                        return Err(format!(
-                            "Invalid type cond argument {}",
-                            cond_obj
-                        ))
-                    }
-                };
-                if cond {
-                    return eval_obj(&list[1], env.clone());
-                }
-            }
-            _ => {
-                return Err(format!(
-                    "Invalid type cond argument {}",
-                    obj
-                ))
+        let clause = match obj.to_vec() {
+            Some(clause) if !clause.is_empty() => clause,
+            _ => return Err(invalid_syntax("cond clause", obj)),
+        };
+        let body = &clause[1..];
+        let body_source = rest_source(obj);
+
+        if clause[0] == else_symbol {
+            if body.is_empty() {
+                return Err(invalid_syntax("cond clause", obj));
             }
+            return eval_sequence(body, &body_source, env);
         }
+        let test = eval_item(&clause, obj, 0, env.clone())?;
+        if !test.is_truthy() {
+            continue;
+        }
+        if body.is_empty() {
+            return Ok(Tail::Return(test));
+        }
+        return eval_sequence(body, &body_source, env);
     }
 
-    Err("No cond clause matched".to_string())
+    Err(EvalErrorKind::Runtime(
+        "No cond clause matched".to_string(),
+    )
+    .into())
 }
 
 fn eval_let(
     list: &[Object],
+    source: &Object,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    if list.len() != 2 {
-        return Err(format!(
-            "Invalid number of arguments for let"
-        ));
+) -> Result<Tail, EvalError> {
+    //  (let bindings body ...)，body 至少有一个表达式
+    if list.len() < 2 {
+        return Err(EvalErrorKind::Arity {
+            name: "let".to_string(),
+            expected: Arity::AtLeast(2),
+            got: list.len(),
+        }
+        .into());
     }
 
     let new_env =
         Rc::new(RefCell::new(Env::extend(env.clone())));
-    let bindings = match &list[0] {
-        Object::List(list) => list.to_vec(),
-        _ => return Err(format!("Invalid let")),
+    let bindings = match list[0].to_vec() {
+        Some(bindings) => bindings,
+        None => {
+            return Err(invalid_syntax("let bindings", &list[0]))
+        }
     };
 
     for obj in bindings {
-        match obj {
-            Object::List(list) => {
+        match obj.to_vec() {
+            Some(list) => {
                 if list.len() != 2 {
-                    return Err(format!(
-                        "Invalid number of arguments for let"
+                    return Err(invalid_syntax(
+                        "let binding",
+                        &obj,
                     ));
                 }
 
                 let name = match &list[0] {
-                    Object::Symbol(name) => name.clone(),
+                    Object::Symbol(name) => *name,
                     _ => {
-                        return Err(format!(
-                            "Invalid let argument {}",
-                            list[0]
+                        return Err(invalid_syntax(
+                            "let binding",
+                            &obj,
                         ))
                     }
                 };
 
-                let value = eval_obj(&list[1], env.clone())?;
+                let value =
+                    eval_item(&list, &obj, 1, env.clone())?;
                 new_env.borrow_mut().set(name, value);
             }
-            _ => {
-                return Err(format!(
-                    "Invalid let argument {}",
-                    obj
-                ))
+            None => {
+                return Err(invalid_syntax("let binding", &obj))
             }
         }
     }
 
-    return eval_obj(&list[1], new_env);
+    eval_sequence(&list[1..], &rest_source(source), new_env)
 }
 
-fn eval_cons(
-    list: &[Object],
-    env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    if list.len() != 2 {
-        return Err(format!(
-            "Invalid number of arguments for cons"
-        ));
+fn eval_quote(list: &[Object]) -> Result<Object, EvalError> {
+    if list.len() != 1 {
+        return Err(form_arity("quote", 1, list.len()));
     }
 
-    let head = eval_obj(&list[0], env.clone())?;
-    let tail = eval_obj(&list[1], env.clone())?;
-
-    //  合并listdata
-    match tail {
-        Object::ListData(mut l) => {
-            l.insert(0, head);
-            Ok(Object::ListData(l))
-        }
-        _ => Err(format!("Invalid type cons argument {}", tail)),
-    }
+    Ok(list[0].clone())
 }
 
-fn eval_keyword(
-    head: &str,
+fn eval_quasiquote(
     list: &[Object],
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    match head {
-        "begin" => eval_begin(list, env.clone()),
-        "define" => eval_define(list, env.clone()),
-        "lambda" => eval_lambda(list, env.clone()),
-        "list" => eval_list(list, env.clone()),
-        "car" => eval_car(list, env.clone()),
-        "cdr" => eval_cdr(list, env.clone()),
-        "length" => eval_length(list, env.clone()),
-        "null?" => eval_is_null(list, env.clone()),
-        "cond" => eval_cond(list, env.clone()),
-        "let" => eval_let(list, env.clone()),
-        "cons" => eval_cons(list, env.clone()),
-        _ => todo!(),
+) -> Result<Object, EvalError> {
+    if list.len() != 1 {
+        return Err(form_arity("quasiquote", 1, list.len()));
     }
+
+    quasiquote_obj(&list[0], 1, env)
 }
 
-fn eval_symbol(
-    name: String,
-    env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    match env.borrow().get(&name) {
-        Some(value) => Ok(value),
-        None => Err(format!("Undefined symbol {}", name)),
+//  (quasiquote x)、(unquote x)、(unquote-splicing x) 形式的 list
+fn quasi_form(obj: &Object) -> Option<(&'static str, Object)> {
+    let mut items = obj.iter();
+    let keyword = match items.next()? {
+        Object::Symbol(keyword) => keyword.as_str(),
+        _ => return None,
+    };
+    let arg = items.next()?;
+    if items.next().is_some() || *items.tail() != Object::Nil {
+        return None;
+    }
+    match keyword {
+        "quasiquote" | "unquote" | "unquote-splicing" => {
+            Some((keyword, arg))
+        }
+        _ => None,
     }
 }
 
-fn eval_obj(
+//  depth 为 quasiquote 的嵌套层数，只有 depth 为 1 的 unquote 才求值
+fn quasiquote_obj(
     obj: &Object,
+    depth: usize,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    let mut current_obj = Box::new(obj.clone());
-    let mut current_env = env.clone();
-    loop {
-        match *current_obj {
-            Object::List(list) => {
-                let head = &list[0];
-
-                match head {
-                    Object::BinaryOp(op) => {
-                        return eval_binary_op(
-                            op,
-                            &list[1..],
-                            current_env,
-                        )
-                    }
-                    Object::Keyword(keyword) => {
-                        //  放在这里，进行尾递归优化
-                        if keyword == "if" {
-                            //  todo 无else 可能
-                            if list.len() != 4 {
-                                return Err(format!("Invalid number of arguments for if"));
-                            }
-
-                            let cond_obj = eval_obj(
-                                &list[1],
-                                current_env.clone(),
-                            )?;
-                            let cond = match cond_obj {
-                                Object::Bool(cond) => cond,
-                                _ => {
-                                    return Err(format!(
-                                        "Condition must be bool"
-                                    ))
-                                }
-                            };
-
-                            if cond {
-                                current_obj =
-                                    Box::new(list[2].clone());
-                            } else {
-                                current_obj =
-                                    Box::new(list[3].clone());
-                            }
-                            continue;
-                        }
-                        return eval_keyword(
-                            keyword,
-                            &list[1..],
-                            current_env,
-                        );
-                    }
-                    Object::Symbol(sym) => {
-                        let lambda =
-                            current_env.borrow().get(sym);
-
-                        if lambda.is_none() {
-                            return Err(format!(
-                                "Unbound function: {}",
-                                sym
-                            ));
-                        }
-
-                        let func = lambda.unwrap();
-                        match func {
-                            Object::Lambda(
-                                params,
-                                body,
-                                func_env,
-                            ) => {
-                                let new_env = Rc::new(
-                                    RefCell::new(Env::extend(
-                                        func_env.clone(),
-                                    )),
-                                );
-
-                                //  传入参数
-                                for (param, arg) in params
-                                    .iter()
-                                    .zip(list[1..].iter())
-                                {
-                                    new_env.borrow_mut().set(
-                                        param.clone(),
-                                        eval_obj(
-                                            arg,
-                                            current_env.clone(),
-                                        )?,
-                                    )
-                                }
-                                current_obj =
-                                    Box::new(Object::List(body));
-                                current_env = new_env;
-                            }
-                            _ => {
-                                return Err(format!(
-                                    "Not a lambda {} {}",
-                                    sym, func
-                                ))
-                            }
-                        }
-                    }
-                    Object::Lambda(params, body, func_env) => {
-                        let new_env = Rc::new(RefCell::new(
-                            Env::extend(func_env.clone()),
-                        ));
-
-                        for (param, arg) in
-                            params.iter().zip(list[1..].iter())
-                        {
-                            new_env.borrow_mut().set(
-                                param.clone(),
-                                eval_obj(
-                                    arg,
-                                    current_env.clone(),
-                                )?,
-                            )
-                        }
-
-                        current_obj =
-                            Box::new(Object::List(body.clone()));
-                        current_env = new_env;
-                    }
-                    _ => {
-                        let mut new_list = vec![];
-                        for obj in list {
-                            let result = eval_obj(
-                                &obj,
-                                current_env.clone(),
-                            )?;
-
-                            if result != Object::Void {
-                                new_list.push(result);
-                            }
-                        }
+) -> Result<Object, EvalError> {
+    if !matches!(obj, Object::Pair(_)) {
+        return Ok(obj.clone());
+    }
+    //  car 方向嵌套很深的 list 逐层递归展开，需要扩展栈空间
+    stack::maybe_grow(|| quasiquote_pair(obj, depth, env))
+}
 
-                        let head = &new_list[0];
-                        match head {
-                            Object::Lambda(_, _, _) => {
-                                current_obj = Box::new(
-                                    Object::List(new_list),
-                                );
-                            }
-                            _ => {
-                                return Ok(Object::List(
-                                    new_list,
-                                ))
-                            }
-                        }
-                    }
-                }
+fn quasiquote_pair(
+    obj: &Object,
+    depth: usize,
+    env: Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    if let Some((keyword, arg)) = quasi_form(obj) {
+        let depth = match keyword {
+            "quasiquote" => depth + 1,
+            "unquote" if depth == 1 => {
+                return eval_obj(&arg, env).map_err(|err| {
+                    err.with_span(&obj.item_span(1))
+                })
             }
-            Object::Integer(i) => return Ok(Object::Integer(i)),
-            Object::Float(f) => return Ok(Object::Float(f)),
-            Object::String(s) => {
-                return Ok(Object::String(s.clone()))
+            "unquote-splicing" if depth == 1 => {
+                return Err(EvalErrorKind::InvalidSyntax(
+                    "unquote-splicing must appear inside a list"
+                        .to_string(),
+                )
+                .into())
             }
-            Object::Symbol(s) => {
-                return eval_symbol(s, current_env)
+            _ => depth - 1,
+        };
+        return Ok(Object::list(vec![
+            Object::Symbol(Symbol::intern(keyword)),
+            quasiquote_obj(&arg, depth, env)?,
+        ]));
+    }
+
+    let mut result = vec![];
+    let mut rest = obj.clone();
+    let mut first = true;
+    while let Object::Pair(pair) = &rest {
+        //  `(a . ,b) 读入后就是 (a unquote b)，剩余部分作为尾部处理
+        if !first && quasi_form(&rest).is_some() {
+            break;
+        }
+        first = false;
+        let (car, cdr) = (pair.car(), pair.cdr());
+        rest = cdr;
+
+        match quasi_form(&car) {
+            Some(("unquote-splicing", arg)) if depth == 1 => {
+                let value = eval_obj(&arg, env.clone())
+                    .map_err(|err| {
+                        err.with_span(&car.item_span(1))
+                    })?;
+                match value.to_vec() {
+                    Some(items) => result.extend(items),
+                    None => {
+                        return Err(EvalError::type_mismatch(
+                            "list", &value,
+                        ))
+                    }
+                }
             }
-            _ => todo!(),
+            _ => result.push(quasiquote_obj(
+                &car,
+                depth,
+                env.clone(),
+            )?),
         }
     }
+    let tail = quasiquote_obj(&rest, depth, env)?;
+    Ok(Object::list_with_tail(result, tail))
 }
 
-pub fn eval(
-    input: &str,
+const SPECIAL_FORMS: [&str; 13] = [
+    "begin",
+    "define",
+    "set!",
+    "lambda",
+    "if",
+    "cond",
+    "let",
+    "and",
+    "or",
+    "quote",
+    "quasiquote",
+    "unquote",
+    "unquote-splicing",
+];
+
+//  特殊形式的结果：直接得到的值，或者处于尾位置、交给 eval_obj_loop 继续求值的表达式
+//  尾位置的表达式不在 Rust 栈上递归，循环写成尾递归时栈空间不会增长
+//  Eval 中的 span 是尾位置的 symbol 在源码中的位置，symbol 自身不记录位置
+enum Tail {
+    Return(Object),
+    Eval(Object, Option<Span>, Rc<RefCell<Env>>),
+}
+
+//  list 是特殊形式的参数，source 是它们在源码中对应的那串 pair
+fn eval_special_form(
+    head: &str,
+    list: &[Object],
+    source: &Object,
     env: Rc<RefCell<Env>>,
-) -> Result<Object, String> {
-    let parsed_list = parse(input);
-    if parsed_list.is_err() {
-        return Err(format!("{}", parsed_list.err().unwrap()));
-    }
+) -> Result<Tail, EvalError> {
+    let result = match head {
+        "begin" => return eval_begin(list, source, env),
+        "if" => return eval_if(list, source, env),
+        "cond" => return eval_cond(list, env),
+        "let" => return eval_let(list, source, env),
+        "and" | "or" => {
+            return eval_and_or(head, list, source, env)
+        }
+        "define" => eval_define(list, source, env.clone()),
+        "set!" => eval_set(list, source, env.clone()),
+        "lambda" => eval_lambda(list, source, env.clone()),
+        "quote" => eval_quote(list),
+        "quasiquote" => eval_quasiquote(list, env.clone()),
+        "unquote" | "unquote-splicing" => {
+            Err(EvalErrorKind::InvalidSyntax(format!(
+                "{} outside of quasiquote",
+                head
+            ))
+            .into())
+        }
+        _ => Err(EvalErrorKind::InvalidSyntax(format!(
+            "Unknown special form {}",
+            head
+        ))
+        .into()),
+    };
+    result.map(Tail::Return)
+}
 
-    eval_obj(&parsed_list.unwrap(), env.clone())
+fn eval_args(
+    list: &[Object],
+    source: &Object,
+    env: Rc<RefCell<Env>>,
+) -> Result<Vec<Object>, EvalError> {
+    (0..list.len())
+        .map(|index| eval_item(list, source, index, env.clone()))
+        .collect()
 }
 
+fn call_builtin(
+    builtin: &Builtin,
+    args: &[Object],
+    env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    if !builtin.arity.accepts(args.len()) {
+        return Err(EvalErrorKind::Arity {
+            name: builtin.name.to_string(),
+            expected: builtin.arity,
+            got: args.len(),
+        }
+        .into());
+    }
+    (builtin.func)(args, env)
+}
+
+//  供内置函数调用传入的过程，如 hash-for-each
+pub(crate) fn apply(
+    func: &Object,
+    args: Vec<Object>,
+    env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match func {
+        Object::Builtin(builtin) => {
+            call_builtin(builtin, &args, env)
+        }
+        Object::Lambda(procedure) => {
+            let new_env = bind_params(
+                procedure.name().unwrap_or("lambda"),
+                &procedure.params,
+                args,
+                procedure.env.clone(),
+            )?;
+            let body = &procedure.body;
+            let source = &procedure.body_source;
+            match eval_sequence(body, source, new_env)? {
+                Tail::Return(value) => Ok(value),
+                Tail::Eval(obj, span, env) => {
+                    eval_obj(&obj, env)
+                        .map_err(|err| err.with_span(&span))
+                }
+            }
+        }
+        _ => {
+            Err(EvalErrorKind::NotAProcedure(func.clone())
+                .into())
+        }
+    }
+}
+
+//  在 lambda 定义时的 env 之上创建新的 env，绑定参数，多余的参数组成 list 绑定到 rest
+//  省略的可选参数在新的 env 中求值默认值，默认值可以引用前面的参数
+//  有关键字参数时，可选参数遇到第一个 keyword 就停止，其余参数必须是 :name value 对
+fn bind_params(
+    name: &str,
+    params: &Params,
+    args: Vec<Object>,
+    func_env: Rc<RefCell<Env>>,
+) -> Result<Rc<RefCell<Env>>, EvalError> {
+    let arity = params.arity();
+    if !arity.accepts(args.len()) {
+        return Err(EvalErrorKind::Arity {
+            name: name.to_string(),
+            expected: arity,
+            got: args.len(),
+        }
+        .into());
+    }
+
+    let new_env = Rc::new(RefCell::new(Env::extend(func_env)));
+    let mut args = args.into_iter().peekable();
+    for (param, arg) in params.required.iter().zip(args.by_ref())
+    {
+        new_env.borrow_mut().set(*param, arg);
+    }
+    for (param, default) in &params.optional {
+        let arg = match args.peek() {
+            Some(Object::Keyword(_))
+                if !params.keys.is_empty() =>
+            {
+                None
+            }
+            _ => args.next(),
+        };
+        let value = bind_default(arg, default, &new_env)?;
+        new_env.borrow_mut().set(*param, value);
+    }
+    if !params.keys.is_empty() {
+        let supplied =
+            keyword_args(name, params, args.by_ref())?;
+        for (param, default) in &params.keys {
+            let arg = supplied
+                .iter()
+                .find(|(keyword, _)| keyword == param)
+                .map(|(_, value)| value.clone());
+            let value = bind_default(arg, default, &new_env)?;
+            new_env.borrow_mut().set(*param, value);
+        }
+    }
+    if let Some(rest) = params.rest {
+        new_env
+            .borrow_mut()
+            .set(rest, Object::list(args.collect()));
+    }
+    Ok(new_env)
+}
+
+//  调用时传入的参数，或者省略时在 env 中求值的默认值，没有默认值则为 Void
+fn bind_default(
+    arg: Option<Object>,
+    default: &Option<Object>,
+    env: &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match (arg, default) {
+        (Some(arg), _) => Ok(arg),
+        (None, Some(default)) => eval_obj(default, env.clone()),
+        (None, None) => Ok(Object::Void),
+    }
+}
+
+//  读取 :name value 对，name 必须是声明过的关键字参数，且只能出现一次
+fn keyword_args(
+    name: &str,
+    params: &Params,
+    mut args: impl Iterator<Item = Object>,
+) -> Result<Vec<(Symbol, Object)>, EvalError> {
+    let mut supplied: Vec<(Symbol, Object)> = vec![];
+    while let Some(arg) = args.next() {
+        let keyword = match arg {
+            Object::Keyword(keyword) => keyword,
+            other => {
+                return Err(EvalError::type_mismatch(
+                    "keyword", &other,
+                ))
+            }
+        };
+        if !params.keys.iter().any(|(key, _)| *key == keyword) {
+            return Err(EvalErrorKind::UnknownKeyword {
+                name: name.to_string(),
+                keyword,
+            }
+            .into());
+        }
+        if supplied.iter().any(|(key, _)| *key == keyword) {
+            return Err(EvalErrorKind::DuplicateKeyword {
+                name: name.to_string(),
+                keyword,
+            }
+            .into());
+        }
+        match args.next() {
+            Some(value) => supplied.push((keyword, value)),
+            None => {
+                return Err(EvalErrorKind::MissingKeywordValue {
+                    name: name.to_string(),
+                    keyword,
+                }
+                .into())
+            }
+        }
+    }
+    Ok(supplied)
+}
+
+fn eval_symbol(
+    name: Symbol,
+    env: Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    match env.borrow().get(name) {
+        Some(value) => Ok(value),
+        None => Err(EvalErrorKind::UnboundVariable(name).into()),
+    }
+}
+
+//  非尾调用的最大嵌套层数，防止无限递归耗尽内存
+const MAX_DEPTH: usize = 10_000;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub(crate) fn eval_obj(
+    obj: &Object,
+    env: Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let depth = DEPTH.with(Cell::get);
+    if depth >= MAX_DEPTH {
+        return Err(EvalErrorKind::Runtime(
+            "Maximum recursion depth exceeded".to_string(),
+        )
+        .into());
+    }
+
+    DEPTH.with(|d| d.set(depth + 1));
+    //  记录当前正在求值的 list 的位置和正在执行的过程，出错时附加到错误上
+    let mut span = None;
+    let mut frame = None;
+    let result = stack::maybe_grow(|| {
+        eval_obj_loop(obj, env, &mut span, &mut frame)
+    });
+    DEPTH.with(|d| d.set(depth));
+    result.map_err(|err| err.with_span(&span).with_frame(frame))
+}
+
+fn eval_obj_loop(
+    obj: &Object,
+    env: Rc<RefCell<Env>>,
+    current_span: &mut Option<Span>,
+    current_frame: &mut Option<Frame>,
+) -> Result<Object, EvalError> {
+    let mut current_obj = obj.clone();
+    let mut current_env = env.clone();
+    loop {
+        //  source 是参数在源码中对应的那串 pair，出错时用来查找 atom 的位置
+        let (list, source) = match &current_obj {
+            Object::Pair(pair) => {
+                if pair.span.is_some() {
+                    *current_span = pair.span.clone();
+                }
+                match current_obj.to_vec() {
+                    Some(list) => (list, pair.cdr()),
+                    None => {
+                        return Err(invalid_syntax(
+                            "form",
+                            &current_obj,
+                        ))
+                    }
+                }
+            }
+            Object::Symbol(s) => {
+                return eval_symbol(*s, current_env)
+            }
+            //  哈希表字面量每次求值得到一个新的表，修改不会影响字面量本身
+            //  key 是字面量，value 按顺序求值
+            Object::HashMap(map) => {
+                let literal = map.borrow().clone();
+                let mut map = IndexMap::new();
+                for (key, value) in literal {
+                    let value =
+                        eval_obj(&value, current_env.clone())?;
+                    map.insert(key, value);
+                }
+                return Ok(Object::HashMap(Rc::new(
+                    RefCell::new(map),
+                )));
+            }
+            //  向量字面量同样每次得到新的向量
+            Object::Vector(items) => {
+                return Ok(Object::vector(
+                    items.borrow().clone(),
+                ))
+            }
+            //  其余的值求值为自身，包括 ()、过程和 Void
+            other => return Ok(other.clone()),
+        };
+        let head = &list[0];
+
+        let func = match head {
+            Object::Symbol(sym) => {
+                //  用户绑定优先，未绑定时才作为特殊形式
+                let bound = current_env.borrow().get(*sym);
+                match bound {
+                    Some(func) => func,
+                    //  尾位置的表达式留在循环中求值，进行尾递归优化
+                    None if SPECIAL_FORMS
+                        .contains(&sym.as_str()) =>
+                    {
+                        match eval_special_form(
+                            sym.as_str(),
+                            &list[1..],
+                            &source,
+                            current_env,
+                        )? {
+                            Tail::Return(value) => {
+                                return Ok(value)
+                            }
+                            Tail::Eval(obj, span, env) => {
+                                if span.is_some() {
+                                    *current_span = span;
+                                }
+                                current_obj = obj;
+                                current_env = env;
+                                continue;
+                            }
+                        }
+                    }
+                    None => {
+                        let err: EvalError =
+                            EvalErrorKind::UnboundVariable(*sym)
+                                .into();
+                        return Err(err.with_span(
+                            &current_obj.item_span(0),
+                        ));
+                    }
+                }
+            }
+            _ => eval_obj(head, current_env.clone())?,
+        };
+
+        let (func, args) = match func {
+            Object::Builtin(_) | Object::Lambda(..) => {
+                let args = eval_args(
+                    &list[1..],
+                    &source,
+                    current_env.clone(),
+                )?;
+                (func, args)
+            }
+            _ => {
+                if let Object::Symbol(_) = head {
+                    return Err(EvalErrorKind::NotAProcedure(
+                        func,
+                    )
+                    .into());
+                }
+
+                //  head 不是过程时，依次求值每个元素
+                let mut new_list = vec![];
+                if func != Object::Void {
+                    new_list.push(func);
+                }
+                for index in 0..list.len() - 1 {
+                    let result = eval_item(
+                        &list[1..],
+                        &source,
+                        index,
+                        current_env.clone(),
+                    )?;
+
+                    if result != Object::Void {
+                        new_list.push(result);
+                    }
+                }
+
+                match new_list.first() {
+                    Some(
+                        Object::Builtin(_) | Object::Lambda(..),
+                    ) => {
+                        let func = new_list.remove(0);
+                        (func, new_list)
+                    }
+                    _ => return Ok(Object::list(new_list)),
+                }
+            }
+        };
+
+        //  优先使用过程自己的名字，匿名过程使用调用处的 symbol
+        let own_name = match &func {
+            Object::Builtin(builtin) => Some(builtin.name),
+            Object::Lambda(procedure) => procedure.name(),
+            _ => None,
+        };
+        let name = match (own_name, head) {
+            (Some(name), _) => name,
+            (None, Object::Symbol(sym)) => sym.as_str(),
+            (None, _) => "lambda",
+        };
+        *current_frame = Some(Frame {
+            name: name.to_string(),
+            span: current_span.clone(),
+        });
+
+        match &func {
+            Object::Builtin(builtin) => {
+                return call_builtin(
+                    builtin,
+                    &args,
+                    &current_env,
+                )
+            }
+            Object::Lambda(procedure) => {
+                current_env = bind_params(
+                    name,
+                    &procedure.params,
+                    args,
+                    procedure.env.clone(),
+                )?;
+                let body = &procedure.body;
+                let source = &procedure.body_source;
+                match eval_sequence(
+                    body,
+                    source,
+                    current_env.clone(),
+                )? {
+                    Tail::Return(value) => return Ok(value),
+                    Tail::Eval(obj, span, env) => {
+                        if span.is_some() {
+                            *current_span = span;
+                        }
+                        current_obj = obj;
+                        current_env = env;
+                    }
+                }
+            }
+            other => {
+                return Err(EvalErrorKind::NotAProcedure(
+                    other.clone(),
+                )
+                .into())
+            }
+        }
+    }
+}
+
+pub fn eval(
+    input: &str,
+    env: Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let parsed_list = parse(input)?;
+    eval_obj(&parsed_list, env.clone())
+}
+
+//  依次求值每个顶层 form，共享同一个 env，返回最后一个的值
+pub fn eval_program(
+    input: &str,
+    env: Rc<RefCell<Env>>,
+) -> Result<Object, EvalError> {
+    let program = parse_spanned_program(input)?;
+
+    //  顶层的 atom 出错时同样指向它在源码中的位置
+    let mut result = Object::Void;
+    for (obj, span) in program {
+        result = eval_obj(&obj, env.clone())
+            .map_err(|err| err.with_span(&Some(span)))?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    type KindCheck = fn(&EvalErrorKind) -> bool;
+
+    //  每个程序求值都应该出错，且错误种类满足对应的检查
+    fn assert_errors(
+        env: &Rc<RefCell<Env>>,
+        cases: &[(&str, KindCheck)],
+    ) {
+        for (program, check) in cases {
+            let err =
+                eval_program(program, env.clone()).unwrap_err();
+            assert!(
+                check(err.kind()),
+                "{}: {:?}",
+                program,
+                err.kind()
+            );
+        }
+    }
+
+    fn is_syntax(kind: &EvalErrorKind, message: &str) -> bool {
+        matches!(
+            kind,
+            EvalErrorKind::InvalidSyntax(m) if m == message
+        )
+    }
+
     #[test]
     fn test_add_int_int() {
         let env = Rc::new(RefCell::new(Env::new()));
@@ -918,6 +1560,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_area_of_a_circle_float() {
         let env = Rc::new(RefCell::new(Env::new()));
         let program = "
@@ -927,10 +1570,7 @@ mod tests {
                     (* pi (* r r))
                 )";
         let result = eval(program, env).unwrap();
-        assert_eq!(
-            result,
-            Object::Float((3.14 * 5.0 * 5.0) as f64)
-        );
+        assert_eq!(result, Object::Float(3.14 * 5.0 * 5.0));
     }
 
     #[test]
@@ -946,7 +1586,7 @@ mod tests {
         let result = eval(program, env).unwrap();
         assert_eq!(
             result,
-            Object::List(vec![Object::String(
+            Object::list(vec![Object::String(
                 "apples mangoes bananas carrots broccoli"
                     .to_string()
             )])
@@ -975,7 +1615,7 @@ mod tests {
                     (sqr 10)
                 )";
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((10 * 10) as i64));
+        assert_eq!(result, Object::Integer(10 * 10));
     }
 
     #[test]
@@ -992,7 +1632,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((89) as i64));
+        assert_eq!(result, Object::Integer(89));
     }
 
     #[test]
@@ -1006,7 +1646,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((120) as i64));
+        assert_eq!(result, Object::Integer(120));
     }
 
     #[test]
@@ -1023,10 +1663,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(
-            result,
-            Object::Integer((314 * 10 * 10) as i64)
-        );
+        assert_eq!(result, Object::Integer(314 * 10 * 10));
     }
 
     #[test]
@@ -1043,10 +1680,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(
-            result,
-            Object::Integer((314 * 10 * 10) as i64)
-        );
+        assert_eq!(result, Object::Integer(314 * 10 * 10));
     }
 
     #[test]
@@ -1063,7 +1697,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((50_005_000) as i64));
+        assert_eq!(result, Object::Integer(50_005_000));
     }
 
     #[test]
@@ -1081,7 +1715,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((3628800) as i64));
+        assert_eq!(result, Object::Integer(3628800));
     }
 
     #[test]
@@ -1098,7 +1732,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((15) as i64));
+        assert_eq!(result, Object::Integer(15));
     }
 
     #[test]
@@ -1117,7 +1751,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((55) as i64));
+        assert_eq!(result, Object::Integer(55));
     }
 
     #[test]
@@ -1130,7 +1764,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((30) as i64));
+        assert_eq!(result, Object::Integer(30));
     }
 
     #[test]
@@ -1143,7 +1777,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((1) as i64));
+        assert_eq!(result, Object::Integer(1));
     }
 
     #[test]
@@ -1158,7 +1792,7 @@ mod tests {
         let result = eval(program, env).unwrap();
         assert_eq!(
             result,
-            Object::ListData(vec![
+            Object::list(vec![
                 Object::Integer(2),
                 Object::Integer(3),
             ])
@@ -1175,7 +1809,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((3) as i64));
+        assert_eq!(result, Object::Integer(3));
     }
 
     #[test]
@@ -1210,7 +1844,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((20) as i64));
+        assert_eq!(result, Object::Integer(20));
     }
 
     #[test]
@@ -1233,7 +1867,7 @@ mod tests {
         let result = eval(program, env).unwrap();
         assert_eq!(
             result,
-            Object::ListData(vec![
+            Object::list(vec![
                 Object::Integer(20),
                 Object::Integer(30),
                 Object::Integer(40),
@@ -1256,7 +1890,7 @@ mod tests {
           ";
 
         let result = eval(program, env).unwrap();
-        assert_eq!(result, Object::Integer((10) as i64));
+        assert_eq!(result, Object::Integer(10));
     }
 
     #[test]
@@ -1315,7 +1949,7 @@ mod tests {
         let result = eval(program, env).unwrap();
         assert_eq!(
             result,
-            Object::ListData(vec![
+            Object::list(vec![
                 Object::Integer(10),
                 Object::Integer(20),
             ])
@@ -1353,6 +1987,63 @@ mod tests {
         assert_eq!(result, Object::Integer(35));
     }
 
+    //  let 的 body 和 cond 分支可以有多个表达式，if 可以没有 else
+    #[test]
+    fn test_bodies() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        for (program, expected) in [
+            ("(let ((x 1)) (define y 2) (+ x y))", "3"),
+            ("(cond ((= 1 1) (define z 1) z))", "1"),
+            (
+                "(cond (false 1) (else (define w 2) (* w w)))",
+                "4",
+            ),
+            ("(cond (false) (2))", "2"),
+            ("(if (= 1 1) 'yes)", "yes"),
+            ("(if (= 1 2) 'yes)", "Void"),
+        ] {
+            let result = eval(program, env.clone()).unwrap();
+            assert_eq!(
+                result.to_string(),
+                expected,
+                "{}",
+                program
+            );
+        }
+
+        assert_errors(
+            &env,
+            &[
+                ("(let ((x 1)))", |k| {
+                    matches!(
+                        k,
+                        EvalErrorKind::Arity {
+                            expected: Arity::AtLeast(2),
+                            got: 1,
+                            ..
+                        }
+                    )
+                }),
+                ("(if 1)", |k| {
+                    matches!(
+                        k,
+                        EvalErrorKind::Arity {
+                            expected: Arity::Range(2, 3),
+                            got: 1,
+                            ..
+                        }
+                    )
+                }),
+                ("(cond (else))", |k| {
+                    is_syntax(k, "Invalid cond clause (else)")
+                }),
+                ("(cond ())", |k| {
+                    is_syntax(k, "Invalid cond clause ()")
+                }),
+            ],
+        );
+    }
+
     #[test]
     fn test_map() {
         let env = Rc::new(RefCell::new(Env::new()));
@@ -1369,7 +2060,7 @@ mod tests {
         let result = eval(program, env).unwrap();
         assert_eq!(
             result,
-            Object::ListData(vec![
+            Object::list(vec![
                 Object::Integer(1),
                 Object::Integer(4),
                 Object::Integer(9),
@@ -1397,7 +2088,7 @@ mod tests {
         let result = eval(program, env).unwrap();
         assert_eq!(
             result,
-            Object::ListData(vec![
+            Object::list(vec![
                 Object::Integer(3),
                 Object::Integer(4),
                 Object::Integer(5),
@@ -1440,4 +2131,1436 @@ mod tests {
         let result = eval(program, env).unwrap();
         assert_eq!(result, Object::Integer(15));
     }
+
+    #[test]
+    fn test_error_span() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "(begin\n  (define x 1)\n  (+ x y))";
+
+        //  出错的是 atom 时指向 atom 本身，而不是包含它的 list
+        let err = eval(program, env.clone()).unwrap_err();
+        let span = err.span().unwrap();
+        assert_eq!((span.line, span.column), (3, 8));
+        assert_eq!(span.text(), "y");
+        assert_eq!(
+            err.to_string(),
+            "Undefined symbol y at 3:8\n  |\n3 |   (+ x y))\n  |        ^"
+        );
+
+        let err = eval("(car 1)", env.clone()).unwrap_err();
+        assert_eq!(err.span().unwrap().text(), "(car 1)");
+
+        let program = "
+(define x 1)
+(define (f a)
+  missing-in-body
+  a)
+(define (g a) (if a missing-in-tail 0))
+(define (h a) (list a) missing-at-end)";
+        eval_program(program, env.clone()).unwrap();
+        for (program, position) in [
+            ("(+ x\n   undefined-y)", (2, 4)),
+            ("(list 1\n  2) foo", (2, 6)),
+            ("(undefined-f\n 1)", (1, 2)),
+            ("(define z\n  missing)", (2, 3)),
+            ("(set! x\n  missing)", (2, 3)),
+            ("(let ((a 1)\n      (b missing))\n  a)", (2, 10)),
+            ("(let ((a 1))\n  missing)", (2, 3)),
+            ("(cond\n  (missing 1))", (2, 4)),
+            ("(and 1\n  missing 2)", (2, 3)),
+            ("(begin 1\n  missing)", (2, 3)),
+            ("`(1\n ,missing)", (2, 3)),
+            ("(f 1)", (4, 3)),
+            ("(g true)", (6, 21)),
+            ("(h 1)", (7, 24)),
+            ("(vector-map h #(1))", (7, 24)),
+        ] {
+            let err =
+                eval_program(program, env.clone()).unwrap_err();
+            let name = match err.kind() {
+                EvalErrorKind::UnboundVariable(name) => {
+                    name.as_str()
+                }
+                other => panic!("{}: {:?}", program, other),
+            };
+            let span = err.span().unwrap();
+            assert_eq!(span.text(), name, "{}", program);
+            assert_eq!(
+                (span.line, span.column),
+                position,
+                "{}",
+                program
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let cases = [
+            (
+                "(+ undefined-var 1)",
+                EvalErrorKind::UnboundVariable(Symbol::intern(
+                    "undefined-var",
+                )),
+            ),
+            (
+                "(car 1)",
+                EvalErrorKind::TypeMismatch {
+                    expected: "pair",
+                    got: Object::Integer(1),
+                },
+            ),
+            (
+                "(car 1 2)",
+                EvalErrorKind::Arity {
+                    name: "car".to_string(),
+                    expected: Arity::Exact(1),
+                    got: 2,
+                },
+            ),
+            ("(/ 1 0)", EvalErrorKind::DivisionByZero),
+            (
+                "(vector-ref #(1) 1)",
+                EvalErrorKind::IndexOutOfRange {
+                    index: 1,
+                    len: 1,
+                },
+            ),
+            (
+                "(hash-ref {} 1)",
+                EvalErrorKind::KeyNotFound(Object::Integer(1)),
+            ),
+            (
+                "(begin (define x 1) (x))",
+                EvalErrorKind::NotAProcedure(Object::Integer(1)),
+            ),
+            (
+                "(error \"bad value\" 1)",
+                EvalErrorKind::User("bad value 1".to_string()),
+            ),
+        ];
+        for (program, kind) in cases {
+            let err = eval(program, env.clone()).unwrap_err();
+            assert_eq!(err.kind(), &kind, "{}", program);
+        }
+
+        let err = eval("(define 1 2)", env.clone()).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            EvalErrorKind::InvalidSyntax(_)
+        ));
+        let err = eval("(car", env).unwrap_err();
+        assert!(matches!(err.kind(), EvalErrorKind::Parse(_)));
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn test_error_frames() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+(define (inner x) (car x))
+(define (outer x) (+ 1 (inner x)))
+(outer 5)";
+
+        //  inner 尾调用 car，不保留自己的 frame
+        let err = eval_program(program, env).unwrap_err();
+        let frames: Vec<_> = err
+            .frames()
+            .iter()
+            .map(|frame| {
+                let span = frame.span.as_ref().unwrap();
+                (frame.name.as_str(), span.text())
+            })
+            .collect();
+        assert_eq!(
+            frames,
+            vec![("car", "(car x)"), ("outer", "(outer 5)")]
+        );
+        assert!(err
+            .to_string()
+            .ends_with("\n  in car at 2:19\n  in outer at 4:1"));
+    }
+
+    #[test]
+    fn test_error_frames_repeated() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+(define (sum n) (if (= n 0) (car n) (+ n (sum (- n 1)))))
+(sum 5000)";
+
+        //  递归调用处相同的 frame 合并为一行
+        let err =
+            eval_program(program, env.clone()).unwrap_err();
+        assert_eq!(err.frames().len(), 5001);
+        assert!(err.to_string().ends_with(
+            "\n  in car at 2:29\
+             \n  in sum at 2:42 (repeated 4999 times)\
+             \n  in sum at 3:1"
+        ));
+
+        //  相互递归无法合并，只显示两端
+        let program = "
+(define (a n) (if (= n 0) (car n) (+ 1 (b (- n 1)))))
+(define (b n) (+ 1 (a n)))
+(a 3000)";
+        let err = eval_program(program, env).unwrap_err();
+        let message = err.to_string();
+        assert_eq!(message.lines().count(), 4 + 2 * 10 + 1);
+        assert!(message.contains("\n  ... 5981 more frames\n"));
+        assert!(message.ends_with("\n  in a at 4:1"));
+    }
+
+    #[test]
+    fn test_quote() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (begin
+              (define xs '(1 (2 3) foo))
+              (list (car xs) (car (cdr xs)) (quote bar))
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(
+            result,
+            Object::list(vec![
+                Object::Integer(1),
+                Object::list(vec![
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]),
+                Object::Symbol(Symbol::intern("bar")),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_quasiquote() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (begin
+              (define x 2)
+              (define ys (list 3 4))
+              `(1 ,x ,@ys (+ 5 ,x) ,@'())
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(result.to_string(), "(1 2 3 4 (+ 5 2))");
+    }
+
+    #[test]
+    fn test_nested_quasiquote() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (begin
+              (define x 1)
+              `(a `(b ,(c ,x)))
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(a (quasiquote (b (unquote (c 1)))))"
+        );
+    }
+
+    #[test]
+    fn test_unquote_outside_quasiquote() {
+        let env = Rc::new(RefCell::new(Env::new()));
+
+        let result = eval("(begin ,x)", env);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_bool_literals() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (begin
+              (define (not x) (if x #f #t))
+              (list (not #t) (not false) (= #t true))
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(
+            result,
+            Object::list(vec![
+                Object::Bool(false),
+                Object::Bool(true),
+                Object::Bool(true),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_nil_literal() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (begin
+              (define xs (cons 1 nil))
+              (list (null? nil) (null? ()) (null? '()) xs)
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(result.to_string(), "(true true true (1))");
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = r"
+          (begin
+              (list #\a #\space #\newline #\x3bb (< #\a #\b) (= #\a #\a))
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(
+            result.to_string(),
+            r"(#\a #\space #\newline #\λ true true)"
+        );
+    }
+
+    #[test]
+    fn test_eval_program() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define pi 314)
+          (define (sqr r) (* r r))
+          (define r 10)
+          (* pi (sqr r))
+          ";
+
+        let result = eval_program(program, env).unwrap();
+        assert_eq!(result, Object::Integer(314 * 10 * 10));
+    }
+
+    #[test]
+    fn test_eval_program_atoms() {
+        let env = Rc::new(RefCell::new(Env::new()));
+
+        let result = eval_program("42", env.clone()).unwrap();
+        assert_eq!(result, Object::Integer(42));
+
+        eval_program("(define x \"hello\")", env.clone())
+            .unwrap();
+        let result = eval_program("x", env.clone()).unwrap();
+        assert_eq!(result, Object::String("hello".to_string()));
+
+        let result = eval_program("", env).unwrap();
+        assert_eq!(result, Object::Void);
+    }
+
+    #[test]
+    fn test_eval_program_shares_env() {
+        let env = Rc::new(RefCell::new(Env::new()));
+
+        eval_program("(define a 1) (define b 2)", env.clone())
+            .unwrap();
+        let result = eval_program("(+ a b)", env).unwrap();
+        assert_eq!(result, Object::Integer(3));
+    }
+
+    #[test]
+    fn test_builtin_names_as_variables() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (begin
+              (define list (cons 1 (cons 2 nil)))
+              (define (count length) (+ length 1))
+              (count (car list))
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(result, Object::Integer(2));
+    }
+
+    #[test]
+    fn test_shadow_builtins_lexically() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (begin
+              (define (f car if) (car if))
+              (list (f (lambda (x) (* x 10)) 4) (car (list 1 2)))
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(
+            result,
+            Object::list(vec![
+                Object::Integer(40),
+                Object::Integer(1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_shadow_special_form() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (begin
+              (let ((quote (lambda (x) (+ x 1))))
+                  (quote 41))
+          )
+          ";
+
+        let result = eval(program, env).unwrap();
+        assert_eq!(result, Object::Integer(42));
+    }
+
+    #[test]
+    fn test_map_builtin() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define (map f l)
+              (if (null? l)
+                  '()
+                  (cons (f (car l)) (map f (cdr l)))))
+          (define pairs '((1 2) (3 4) (5 6)))
+          (map car pairs)
+          ";
+
+        let result = eval_program(program, env).unwrap();
+        assert_eq!(
+            result,
+            Object::list(vec![
+                Object::Integer(1),
+                Object::Integer(3),
+                Object::Integer(5),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_fold_left_builtin() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define (fold-left f acc l)
+              (if (null? l)
+                  acc
+                  (fold-left f (f acc (car l)) (cdr l))))
+          (fold-left + 0 '(1 2 3 4 5))
+          ";
+
+        let result = eval_program(program, env).unwrap();
+        assert_eq!(result, Object::Integer(15));
+    }
+
+    #[test]
+    fn test_builtin_as_value() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define first car)
+          (list (first '(7 8)) car)
+          ";
+
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result.to_string(), "(7 Builtin(car))");
+
+        let result = eval("((if true + -) 5 3)", env.clone());
+        assert_eq!(result.unwrap(), Object::Integer(8));
+
+        let result = eval("(car 1 2)", env).unwrap_err();
+        assert_eq!(
+            result.to_string().lines().next(),
+            Some(
+                "Invalid number of arguments for car: expected 1, got 2 at 1:1"
+            )
+        );
+    }
+
+    #[test]
+    fn test_dotted_pairs() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define p (cons 1 2))
+          (list (car p) (cdr p) (pair? p) (list? p) (list? '(1 2)))
+          ";
+
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result.to_string(), "(1 2 true false true)");
+
+        let result = eval("(cons 1 (cons 2 3))", env.clone());
+        assert_eq!(result.unwrap().to_string(), "(1 2 . 3)");
+
+        let result = eval("'(1 (2 . 3) . 4)", env.clone());
+        assert_eq!(
+            result.unwrap().to_string(),
+            "(1 (2 . 3) . 4)"
+        );
+
+        let result = eval("`(1 . ,(+ 1 1))", env.clone());
+        assert_eq!(result.unwrap().to_string(), "(1 . 2)");
+
+        let result = eval("(list (pair? '()) (null? '()))", env);
+        assert_eq!(result.unwrap().to_string(), "(false true)");
+    }
+
+    #[test]
+    fn test_long_list() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define (build n acc)
+              (if (= n 0) acc (build (- n 1) (cons n acc))))
+          (define (last l)
+              (if (null? (cdr l)) (car l) (last (cdr l))))
+          (define l (build 100000 '()))
+          (list (length l) (car l) (last l))
+          ";
+
+        let result = eval_program(program, env).unwrap();
+        assert_eq!(result.to_string(), "(100000 1 100000)");
+    }
+
+    #[test]
+    fn test_code_is_data() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define x 10)
+          (define code (list '+ 'x 5))
+          (list (eval code) (eval ''(1 2)) (= (list 1 2) '(1 2)))
+          ";
+
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result.to_string(), "(15 (1 2) true)");
+
+        let program = "
+          (define (make-adder n) `(lambda (x) (+ x ,n)))
+          ((eval (make-adder 3)) 4)
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result, Object::Integer(7));
+
+        let result = eval("(car '(if 1 2 3))", env.clone());
+        assert_eq!(
+            result.unwrap(),
+            Object::Symbol(Symbol::intern("if"))
+        );
+
+        let err = eval("(eval (cons '+ 1))", env).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid form (+ . 1)"));
+    }
+
+    #[test]
+    fn test_symbol_string_conversion() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define sym (string->symbol \"car\"))
+          (list (= (symbol->string 'foo) \"foo\")
+                ((eval sym) '(1 2)))
+          ";
+
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result.to_string(), "(true 1)");
+
+        let result =
+            eval("(string->symbol \"a b\")", env).unwrap();
+        assert_eq!(
+            result,
+            Object::Symbol(Symbol::intern("a b"))
+        );
+    }
+
+    #[test]
+    fn test_bignum_promotion() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define (fact n) (if (< n 1) 1 (* n (fact (- n 1)))))
+          (fact 25)
+          ";
+
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "15511210043330985984000000"
+        );
+
+        let result =
+            eval("(/ (fact 25) (fact 24))", env.clone());
+        assert_eq!(result.unwrap(), Object::Integer(25));
+
+        let result =
+            eval("(- 9223372036854775807 -1)", env.clone());
+        assert_eq!(
+            result.unwrap().to_string(),
+            "9223372036854775808"
+        );
+
+        let program = "(list (< 9223372036854775807 100000000000000000000)
+                             (= 100000000000000000000 100000000000000000000)
+                             (* 100000000000000000000 0.5))";
+        let result = eval(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(true true 50000000000000000000)"
+        );
+
+        let err = eval("(% (fact 25) 0)", env).unwrap_err();
+        assert!(err.to_string().starts_with("Division by zero"));
+    }
+
+    #[test]
+    fn test_rationals() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let cases = [
+            ("(/ 1 2)", "1/2"),
+            ("(+ 1/3 2/3)", "1"),
+            ("(* 2/3 3/4)", "1/2"),
+            ("(- 1/2 1)", "-1/2"),
+            ("(+ 1/2 0.25)", "0.75"),
+            ("(/ 6/4 3)", "1/2"),
+            ("(numerator 6/4)", "3"),
+            ("(denominator 6/4)", "2"),
+            ("(denominator 5)", "1"),
+            ("(numerator 0.5)", "1"),
+            ("(exact->inexact 1/4)", "0.25"),
+            ("(inexact->exact 0.25)", "1/4"),
+            ("(inexact->exact 2.0)", "2"),
+            ("(quotient -7 2)", "-3"),
+            ("(remainder -7 2)", "-1"),
+            ("(modulo -7 2)", "1"),
+            ("(modulo 7.0 -2)", "-1"),
+            (
+                "(list (< 1/3 0.5) (= 1/2 0.5) (> 1 0.5))",
+                "(true true true)",
+            ),
+            ("(% 7.5 2)", "1.5"),
+        ];
+        for (program, expected) in cases {
+            let result = eval(program, env.clone()).unwrap();
+            assert_eq!(
+                result.to_string(),
+                expected,
+                "{}",
+                program
+            );
+        }
+
+        let errors = [
+            ("(/ 1/2 0)", "Division by zero"),
+            (
+                "(quotient 1/2 1)",
+                "Invalid type: expected integer, got 1/2",
+            ),
+            (
+                "(inexact->exact +nan.0)",
+                "Invalid type: expected finite number, got NaN",
+            ),
+        ];
+        for (program, expected) in errors {
+            let err = eval(program, env.clone()).unwrap_err();
+            assert!(
+                err.to_string().starts_with(expected),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash_map() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define h {\"one\" 1 two 2})
+          (hash-set! h 3 3)
+          (hash-set! h 'two 22)
+          (hash-remove! h \"one\")
+          (list (hash-ref h 'two)
+                (hash-ref h 3)
+                (hash-ref h \"one\" 'missing)
+                (hash-count h)
+                (hash-has-key? h 'two))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result.to_string(), "(22 3 missing 2 true)");
+
+        let program = "
+          (define h (make-hash '((a . 1))))
+          (define total (make-hash))
+          (hash-set! total 'sum 0)
+          (hash-for-each h (lambda (k v)
+              (hash-set! total 'sum (+ (hash-ref total 'sum) v))))
+          (list (hash-keys h)
+                (hash-values h)
+                (hash->list h)
+                (hash-map h (lambda (k v) (* v 10)))
+                (hash-ref total 'sum))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "((a) (1) ((a . 1)) (10) 1)"
+        );
+
+        //  每次求值字面量都得到新的哈希表
+        let program = "
+          (define (fresh) {})
+          (define h (fresh))
+          (hash-set! h 1 1)
+          (hash-count (fresh))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result, Object::Integer(0));
+
+        //  字面量的 value 在求值时求值，quote 之后保持原样
+        //  遍历和显示按插入的顺序，删除不影响其余 key 的顺序
+        let program = "
+          (define x 5)
+          (define h {:a (+ 1 2) b x \"c\" 'quoted})
+          (define first (hash-ref h :a))
+          (hash-set! h 1 'one)
+          (hash-remove! h :a)
+          (hash-set! h :a 'again)
+          (list first h (hash-keys h) '{:a (+ 1 2)})
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(3 {b 5 c quoted 1 one :a again} (b c 1 :a) \
+             {:a (+ 1 2)})"
+        );
+
+        let err =
+            eval("(hash-ref {} 1)", env.clone()).unwrap_err();
+        assert!(err.to_string().starts_with("Key 1 not found"));
+        let err = eval("(hash-set! car 1 1)", env.clone())
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid type: expected hash map"));
+        let err = eval("(hash-set! {} {} 1)", env.clone())
+            .unwrap_err();
+        assert!(err.to_string().starts_with(
+            "Invalid type: expected hashable value"
+        ));
+        //  pair 可以被 set-car! 修改，不能作为 key
+        let program = "
+          (define k (list 1 2))
+          (hash-set! (make-hash) k 1)
+          ";
+        let err = eval_program(program, env).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            EvalErrorKind::TypeMismatch {
+                expected: "hashable value",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_set() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define (make-counter)
+            (let ((n 0))
+              (lambda () (set! n (+ n 1)) n)))
+          (define c1 (make-counter))
+          (define c2 (make-counter))
+          (c1)
+          (c1)
+          (c2)
+          (list (c1) (c2))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result.to_string(), "(3 2)");
+
+        //  set! 修改外层的绑定，不在当前 env 新建绑定
+        let program = "
+          (define x 1)
+          (define (f) (set! x 2))
+          (f)
+          x
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result, Object::Integer(2));
+
+        let err = eval("(set! undefined-var 1)", env.clone())
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Undefined symbol"));
+        let err = eval("(set! 1 1)", env).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid set! target"));
+    }
+
+    #[test]
+    fn test_mutable_pairs_and_boxes() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define tail (list 2 3))
+          (define l (cons 1 tail))
+          (set-car! tail 20)
+          (set-cdr! (cdr tail) '(4))
+          (define b (box 1))
+          (define alias b)
+          (set-box! alias (+ (unbox b) 1))
+          (list l (unbox b) (box? b) (box? 1) b)
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "((1 20 3 4) 2 true false #&2)"
+        );
+
+        let err =
+            eval("(set-car! '() 1)", env.clone()).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid type: expected pair"));
+        let err = eval("(unbox 1)", env).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid type: expected box"));
+    }
+
+    #[test]
+    fn test_cycles() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define x (list 1 2))
+          (set-cdr! (cdr x) x)
+          (define y (list 1))
+          (set-car! y y)
+          (define v (vector 1))
+          (vector-set! v 0 v)
+          (define b (box 1))
+          (set-box! b (list b))
+          (define h (make-hash))
+          (hash-set! h 'self h)
+          (define shared (list 1))
+          (list (list? x) x y v b h (list shared shared))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(false (1 2 . #<cycle>) (#<cycle>) #(#<cycle>) \
+             #&(#<cycle>) {self #<cycle>} ((1) (1)))"
+        );
+
+        let err = eval("(length x)", env.clone()).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            EvalErrorKind::TypeMismatch {
+                expected: "list",
+                ..
+            }
+        ));
+        assert!(err.to_string().starts_with(
+            "Invalid type: expected list, got (1 2 . #<cycle>)"
+        ));
+        //  带环的 list 作为代码求值
+        let err = eval("(eval (cons 'list x))", env.clone())
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            EvalErrorKind::InvalidSyntax(_)
+        ));
+        let err =
+            eval("(list->vector x)", env.clone()).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            EvalErrorKind::TypeMismatch {
+                expected: "list",
+                ..
+            }
+        ));
+
+        //  两个各自带环的 list 按展开后的无限序列比较
+        let program = "
+          (define a (list 1))
+          (set-cdr! a a)
+          (define b (list 1))
+          (set-cdr! b b)
+          (define c (list 1 2))
+          (set-cdr! (cdr c) c)
+          (define d (list y))
+          (set-car! d d)
+          (list (= a b) (= a c) (= x c) (= y d) (= x '(1 2)))
+          ";
+        let result = eval_program(program, env).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(true false true true false)"
+        );
+    }
+
+    #[test]
+    fn test_vector() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define v (make-vector 3 'x))
+          (vector-set! v 0 1)
+          (define w (list->vector '(1 2 3 4)))
+          (list v
+                (vector-ref v 0)
+                (vector-length w)
+                (vector->list w)
+                (vector-map (lambda (x) (* x x)) w)
+                (vector-copy w 1 3)
+                (vector-copy w 2)
+                (vector? w)
+                (vector? '(1)))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(#(1 x x) 1 4 (1 2 3 4) #(1 4 9 16) #(2 3) #(3 4) \
+             true false)"
+        );
+
+        //  字面量不求值元素，每次求值得到新的向量
+        let program = "
+          (define (fresh) #(a (b)))
+          (define v (fresh))
+          (vector-fill! v 0)
+          (list v (fresh))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result.to_string(), "(#(0 0) #(a (b)))");
+
+        assert_errors(
+            &env,
+            &[
+                ("(vector-ref #(1 2 3) 3)", |k| {
+                    *k == EvalErrorKind::IndexOutOfRange {
+                        index: 3,
+                        len: 3,
+                    }
+                }),
+                ("(vector-set! #() 0 1)", |k| {
+                    *k == EvalErrorKind::IndexOutOfRange {
+                        index: 0,
+                        len: 0,
+                    }
+                }),
+                ("(vector-ref #(1) -1)", |k| {
+                    *k == EvalErrorKind::IndexOutOfRange {
+                        index: -1,
+                        len: 1,
+                    }
+                }),
+                ("(vector-copy #(1 2) 1 5)", |k| {
+                    *k == EvalErrorKind::IndexOutOfRange {
+                        index: 5,
+                        len: 2,
+                    }
+                }),
+                ("(vector-copy #(1 2) 2 1)", |k| {
+                    *k == EvalErrorKind::IndexOutOfRange {
+                        index: 2,
+                        len: 2,
+                    }
+                }),
+                ("(vector-ref '(1) 0)", |k| {
+                    matches!(
+                        k,
+                        EvalErrorKind::TypeMismatch {
+                            expected: "vector",
+                            ..
+                        }
+                    )
+                }),
+                ("(make-vector -1)", |k| {
+                    *k == EvalErrorKind::TypeMismatch {
+                        expected: "non-negative integer",
+                        got: Object::Integer(-1),
+                    }
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_variadic_lambda() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define (f a b . rest) (list a b rest))
+          (define (g . xs) xs)
+          (define h (lambda args (length args)))
+          (list (f 1 2) (f 1 2 3 4) (g) (g 1 2) (h 1 2 3))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "((1 2 ()) (1 2 (3 4)) () (1 2) 3)"
+        );
+
+        let result =
+            eval("(lambda (a . rest) a)", env.clone()).unwrap();
+        assert_eq!(result.to_string(), "Lambda(a . rest) a");
+
+        assert_errors(
+            &env,
+            &[
+                ("(lambda (a . 1) a)", |k| {
+                    is_syntax(k, "Invalid lambda parameter 1")
+                }),
+                ("(lambda (a 1) a)", |k| {
+                    is_syntax(k, "Invalid lambda parameter 1")
+                }),
+                ("(lambda (a a) a)", |k| {
+                    is_syntax(k, "Duplicate lambda parameter a")
+                }),
+                ("(lambda (a . a) a)", |k| {
+                    is_syntax(k, "Duplicate lambda parameter a")
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_lambda_body() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        //  body 可以是任意表达式，也可以有多个表达式，最后一个的值作为结果
+        let program = "
+          (define (id x) x)
+          (define (sum-squares a b)
+            (define (square x) (* x x))
+            (+ (square a) (square b)))
+          (define log '())
+          (define (noisy x)
+            (set! log (cons x log))
+            (set! log (cons x log))
+            x)
+          (list ((lambda (x) x) 1) (id 'a) (sum-squares 3 4)
+                (noisy 5) log)
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result.to_string(), "(1 a 25 5 (5 5))");
+
+        let result =
+            eval("(lambda (x) (display x) x)", env.clone())
+                .unwrap();
+        assert_eq!(
+            result.to_string(),
+            "Lambda(x) (display x) x"
+        );
+
+        //  body 内的 define 只在调用时的 env 中可见
+        let err = eval("(square 2)", env.clone()).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            EvalErrorKind::UnboundVariable(_)
+        ));
+        let err = eval("(lambda (x))", env).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            EvalErrorKind::Arity {
+                expected: Arity::AtLeast(2),
+                got: 1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_nary_arithmetic() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        for (program, expected) in [
+            ("(+)", "0"),
+            ("(*)", "1"),
+            ("(+ 5)", "5"),
+            ("(* 5)", "5"),
+            ("(+ 1 2 3 4)", "10"),
+            ("(* 1 2 3 4)", "24"),
+            ("(- 5)", "-5"),
+            ("(- 10 1 2 3)", "4"),
+            ("(/ 2)", "1/2"),
+            ("(/ 0.5)", "2"),
+            ("(/ 60 2 3)", "10"),
+            ("(+ \"a\" \"b\" \"c\")", "abc"),
+            ("(< 1 2 3)", "true"),
+            ("(< 1 3 2)", "false"),
+            ("(> 3 2 1)", "true"),
+            ("(= 1 1 1)", "true"),
+            ("(= 1 1 2)", "false"),
+            ("(< 1)", "true"),
+        ] {
+            let result = eval(program, env.clone()).unwrap();
+            assert_eq!(
+                result.to_string(),
+                expected,
+                "{}",
+                program
+            );
+        }
+
+        assert_errors(
+            &env,
+            &[
+                ("(-)", |k| {
+                    *k == EvalErrorKind::Arity {
+                        name: "-".to_string(),
+                        expected: Arity::AtLeast(1),
+                        got: 0,
+                    }
+                }),
+                ("(<)", |k| {
+                    *k == EvalErrorKind::Arity {
+                        name: "<".to_string(),
+                        expected: Arity::AtLeast(1),
+                        got: 0,
+                    }
+                }),
+                ("(/ 1 2 0)", |k| {
+                    matches!(k, EvalErrorKind::DivisionByZero)
+                }),
+                ("(/ 0)", |k| {
+                    matches!(k, EvalErrorKind::DivisionByZero)
+                }),
+                ("(< 1 3 'a)", |k| {
+                    *k == EvalErrorKind::TypeMismatch {
+                        expected: "number",
+                        got: Object::Symbol(Symbol::intern("a")),
+                    }
+                }),
+                ("(< 'a)", |k| {
+                    *k == EvalErrorKind::TypeMismatch {
+                        expected: "number",
+                        got: Object::Symbol(Symbol::intern("a")),
+                    }
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_lambda_arity() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let program = "
+          (define (greet name (greeting \"hi\"))
+            (+ greeting \" \" name))
+          (define (f a #!optional b) (list a b))
+          (define (g a (b (* a 2)) . rest) (list a b rest))
+          (list (greet \"bob\") (greet \"bob\" \"yo\")
+                (f 1 2) (g 1) (g 1 5) (g 1 5 6 7))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(hi bob yo bob (1 2) (1 2 ()) (1 5 ()) (1 5 (6 7)))"
+        );
+        let result = eval("(f 1)", env.clone()).unwrap();
+        assert_eq!(
+            result,
+            Object::list(vec![Object::Integer(1), Object::Void])
+        );
+
+        let result = eval(
+            "(lambda (a #!optional b (c 1) . d) a)",
+            env.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.to_string(),
+            "Lambda(a #!optional b (c 1) . d) a"
+        );
+
+        for (program, expected) in [
+            ("(procedure-arity car)", "(1 . 1)"),
+            ("(procedure-arity list)", "(0 . false)"),
+            ("(procedure-arity greet)", "(1 . 2)"),
+            ("(procedure-arity g)", "(1 . false)"),
+            ("(procedure-arity (lambda () (list)))", "(0 . 0)"),
+        ] {
+            let result = eval(program, env.clone()).unwrap();
+            assert_eq!(
+                result.to_string(),
+                expected,
+                "{}",
+                program
+            );
+        }
+
+        assert_errors(
+            &env,
+            &[
+                ("(greet)", |k| {
+                    *k == EvalErrorKind::Arity {
+                        name: "greet".to_string(),
+                        expected: Arity::Range(1, 2),
+                        got: 0,
+                    }
+                }),
+                ("(greet 1 2 3)", |k| {
+                    *k == EvalErrorKind::Arity {
+                        name: "greet".to_string(),
+                        expected: Arity::Range(1, 2),
+                        got: 3,
+                    }
+                }),
+                ("((lambda (x) (list x)) 1 2)", |k| {
+                    *k == EvalErrorKind::Arity {
+                        name: "lambda".to_string(),
+                        expected: Arity::Exact(1),
+                        got: 2,
+                    }
+                }),
+                ("(g)", |k| {
+                    *k == EvalErrorKind::Arity {
+                        name: "g".to_string(),
+                        expected: Arity::AtLeast(1),
+                        got: 0,
+                    }
+                }),
+                (
+                    "(vector-map (lambda (x y) (list x)) #(1))",
+                    |k| {
+                        *k == EvalErrorKind::Arity {
+                            name: "lambda".to_string(),
+                            expected: Arity::Exact(2),
+                            got: 1,
+                        }
+                    },
+                ),
+                ("(lambda ((a 1) b) a)", |k| {
+                    is_syntax(
+                        k,
+                        "Required parameter b \
+                         after optional parameters",
+                    )
+                }),
+                ("(lambda (#!optional a a) a)", |k| {
+                    is_syntax(k, "Duplicate lambda parameter a")
+                }),
+                ("(lambda ((a)) a)", |k| {
+                    is_syntax(k, "Invalid lambda parameter (a)")
+                }),
+                ("(procedure-arity 1)", |k| {
+                    *k == EvalErrorKind::TypeMismatch {
+                        expected: "procedure",
+                        got: Object::Integer(1),
+                    }
+                }),
+            ],
+        );
+
+        //  错误记录了调用的位置
+        let err = eval_program(
+            "(define (h x) (list x))\n(h)",
+            env.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err.frames()[0].name, "h");
+
+        //  报告 define 时记下的名字，而不是调用处的名字
+        for (program, name) in [
+            ("(define h2 h) (h2)", "h"),
+            ("(define k (lambda (x) x)) (k)", "k"),
+            ("(vector-map h #(1) #(2))", "vector-map"),
+            ("(vector-map (lambda () 1) #(1))", "lambda"),
+            (
+                "(define (two a b) a) (vector-map two #(1))",
+                "two",
+            ),
+            ("(let ((anon (lambda () 1))) (anon 1))", "anon"),
+        ] {
+            let err =
+                eval_program(program, env.clone()).unwrap_err();
+            match err.kind() {
+                EvalErrorKind::Arity { name: got, .. } => {
+                    assert_eq!(got, name, "{}", program)
+                }
+                other => panic!("{}: {:?}", program, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_keyword_arguments() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        let result = eval(
+            "(list :port ':port (keyword? :a) (keyword? 'a))",
+            env.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(:port :port true false)"
+        );
+        //  关键字可以作为哈希表的 key
+        let result =
+            eval("(hash-ref {:a 1 :b 2} :b)", env.clone())
+                .unwrap();
+        assert_eq!(result, Object::Integer(2));
+
+        let program = "
+          (define (make-server host #!key (port 80) verbose)
+            (list host port verbose))
+          (define (f a #!optional (b 2) #!key (c (+ a b)))
+            (list a b c))
+          (list (make-server \"h\")
+                (make-server \"h\" :verbose true :port 8080)
+                (f 1) (f 1 5) (f 1 :c 0) (f 1 5 :c 0))
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(
+            result.to_string(),
+            "((h 80 Void) (h 8080 true) (1 2 3) (1 5 6) (1 2 0) (1 5 0))"
+        );
+
+        let result =
+            eval("(lambda (a #!key b (c 1)) a)", env.clone())
+                .unwrap();
+        assert_eq!(
+            result.to_string(),
+            "Lambda(a #!key b (c 1)) a"
+        );
+        let result =
+            eval("(procedure-arity make-server)", env.clone())
+                .unwrap();
+        assert_eq!(result.to_string(), "(1 . 5)");
+
+        assert_errors(
+            &env,
+            &[
+                ("(make-server \"h\" :prot 8080)", |k| {
+                    *k == EvalErrorKind::UnknownKeyword {
+                        name: "make-server".to_string(),
+                        keyword: Symbol::intern("prot"),
+                    }
+                }),
+                ("(make-server \"h\" :port 1 :port 2)", |k| {
+                    *k == EvalErrorKind::DuplicateKeyword {
+                        name: "make-server".to_string(),
+                        keyword: Symbol::intern("port"),
+                    }
+                }),
+                ("(make-server \"h\" :port)", |k| {
+                    *k == EvalErrorKind::MissingKeywordValue {
+                        name: "make-server".to_string(),
+                        keyword: Symbol::intern("port"),
+                    }
+                }),
+                ("(make-server \"h\" 8080 1)", |k| {
+                    *k == EvalErrorKind::TypeMismatch {
+                        expected: "keyword",
+                        got: Object::Integer(8080),
+                    }
+                }),
+                ("(lambda (#!key a . rest) a)", |k| {
+                    is_syntax(
+                        k,
+                        "Rest parameter rest \
+                         cannot be used with #!key",
+                    )
+                }),
+                ("(lambda (#!key a #!optional b) a)", |k| {
+                    is_syntax(
+                        k,
+                        "Invalid lambda parameter #!optional",
+                    )
+                }),
+                ("(lambda (a #!key a) a)", |k| {
+                    is_syntax(k, "Duplicate lambda parameter a")
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_and_or() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        for (program, expected) in [
+            ("(and)", "true"),
+            ("(or)", "false"),
+            ("(and 1 2 3)", "3"),
+            ("(and 1 false 3)", "false"),
+            ("(or false 2 3)", "2"),
+            ("(or false false)", "false"),
+            ("(and '() 1)", "1"),
+        ] {
+            let result = eval(program, env.clone()).unwrap();
+            assert_eq!(
+                result.to_string(),
+                expected,
+                "{}",
+                program
+            );
+        }
+
+        //  短路求值，后面的表达式不会执行
+        let program = "
+          (define x 0)
+          (and false (set! x 1))
+          (or true (set! x 2))
+          x
+          ";
+        let result = eval_program(program, env.clone()).unwrap();
+        assert_eq!(result, Object::Integer(0));
+
+        //  if、cond 与 and、or 使用同一个真假规则
+        let program = "
+          (list (if (and 1 2) 'a 'b)
+                (if (or false '()) 'a 'b)
+                (if 0 'a 'b)
+                (cond ((and 1 false) 'a) ((or false 2) 'b) (else 'c))
+                (if (and) 'a 'b)
+                (if (or) 'a 'b))
+          ";
+        let result = eval_program(program, env).unwrap();
+        assert_eq!(result.to_string(), "(a a a b a b)");
+    }
+
+    #[test]
+    fn test_procedure_equality() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        //  过程按同一性比较，env 中包含过程自身时也能结束
+        let program = "
+          (define (f) (+ 1 1))
+          (define (make) (lambda () 1))
+          (list (= (list f) (list f))
+                (= (list (make)) (list (make)))
+                (= (list car) (list car)))
+          ";
+        let result = eval_program(program, env).unwrap();
+        assert_eq!(result.to_string(), "(true false true)");
+    }
+
+    #[test]
+    fn test_structural_equality() {
+        let env = Rc::new(RefCell::new(Env::new()));
+        for (program, expected) in [
+            ("(= 'a 'a)", "true"),
+            ("(= 'a 'b)", "false"),
+            ("(= 'a 'a 'a)", "true"),
+            ("(= :a :a)", "true"),
+            ("(= :a 'a)", "false"),
+            ("(= #(1 (2)) (vector 1 (list 2)))", "true"),
+            ("(= #(1) #(2))", "false"),
+            ("(= '(a #(b)) (list 'a (vector 'b)))", "true"),
+            ("(= 1 'a)", "false"),
+            ("(= \"1\" 1)", "false"),
+            ("(= car car)", "true"),
+        ] {
+            let result = eval(program, env.clone()).unwrap();
+            assert_eq!(
+                result.to_string(),
+                expected,
+                "{}",
+                program
+            );
+        }
+
+        //  关键字可以用来分派
+        let program = "
+          (define (describe k)
+            (cond ((= k :small) 1) ((= k :large) 100) (else 0)))
+          (list (describe :small)
+                (describe :large)
+                (describe 'x))
+          ";
+        let result = eval_program(program, env).unwrap();
+        assert_eq!(result.to_string(), "(1 100 0)");
+    }
 }
@@ -1,99 +1,387 @@
-use std::{collections::HashSet, result, str::Chars};
+use std::{fmt, rc::Rc, str::Chars};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+
+use crate::span::Span;
+use crate::symbol::Symbol;
 
 #[derive(Debug, PartialEq)]
 pub enum Token {
     Integer(i64),
-    Symbol(String),
+    //  超出 i64 范围的整数字面量
+    BigInt(BigInt),
+    //  1/3 这样的分数字面量，尚未约分为整数
+    Rational(BigRational),
+    Symbol(Symbol),
+    //  :name 关键字，不含开头的冒号
+    Keyword(Symbol),
     LParen,
     RParen,
+    //  {k v ...} 哈希表字面量
+    LBrace,
+    RBrace,
+    //  #( 向量字面量的开头，以 RParen 结束
+    VectorOpen,
     Float(f64),
     String(String),
-    BinaryOp(String),
-    Keyword(String),
+    Bool(bool),
+    Char(char),
+    Nil,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+    //  (a . b) 中的点，前后必须是分隔符
+    Dot,
+    //  #; 注释掉紧随其后的一个 datum，由 parser 跳过
+    DatumComment,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
 }
 
-#[derive(Debug)]
-pub struct TokenError;
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenError {
+    pub kind: TokenErrorKind,
+    pub err: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenErrorKind {
+    UnexpectedChar,
+    UnterminatedString,
+    UnterminatedComment,
+    InvalidEscape,
+    InvalidNumber,
+    //  无法识别的 #t/#f、#\ 字符等
+    InvalidLiteral,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.err, self.span)
+    }
+}
 
 struct Tokenizer<'a> {
     input: Chars<'a>,
     current_char: Option<char>,
-    keywords: HashSet<&'a str>,
-    binary_ops: HashSet<char>,
+    source: Rc<str>,
+    //  current_char 的字节偏移与行列号
+    pos: usize,
+    line: usize,
+    column: usize,
 }
 
 impl Tokenizer<'_> {
-    pub fn new(input: &str) -> Tokenizer {
+    pub fn new(input: &str) -> Tokenizer<'_> {
+        let source = Rc::from(input);
         let mut input = input.chars();
         let current_char = input.next();
 
-        let keywords = vec![
-            "define", "lambda", "list", "print", "range",
-            "cons", "car", "cdr", "length", "null?", "begin",
-            "let", "if", "else", "cond",
-        ]
-        .into_iter()
-        .collect::<HashSet<&str>>();
-
-        let binary_ops: HashSet<char> = vec![
-            '+', '-', '*', '/', '%', '<', '>', '=', '|', '&',
-        ]
-        .into_iter()
-        .collect::<HashSet<char>>();
-
         Tokenizer {
             input,
             current_char,
-            keywords,
-            binary_ops,
+            source,
+            pos: 0,
+            line: 1,
+            column: 1,
         }
     }
     fn advance(&mut self) {
+        if let Some(c) = self.current_char {
+            self.pos += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
         self.current_char = self.input.next();
     }
 
-    fn eat_whitespace(&mut self) {
+    fn peek_char(&self) -> Option<char> {
+        self.input.clone().next()
+    }
+
+    //  跳过空白以及 ; 行注释和 #| |# 块注释
+    fn eat_whitespace(&mut self) -> Result<(), TokenError> {
+        loop {
+            match self.current_char {
+                Some(c) if c.is_whitespace() => self.advance(),
+                Some(';') => self.eat_line_comment(),
+                Some('#') if self.peek_char() == Some('|') => {
+                    self.eat_block_comment()?
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn eat_line_comment(&mut self) {
         while let Some(c) = self.current_char {
-            if !c.is_whitespace() {
+            if c == '\n' {
                 break;
             }
             self.advance();
         }
     }
 
-    fn read_string(&mut self) -> String {
-        let mut result = String::new();
-        self.advance();
+    //  块注释可以嵌套
+    fn eat_block_comment(&mut self) -> Result<(), TokenError> {
+        let (start, line, column) =
+            (self.pos, self.line, self.column);
+        let mut depth = 0;
         while let Some(c) = self.current_char {
-            if c == '"' {
-                break;
+            if c == '#' && self.peek_char() == Some('|') {
+                depth += 1;
+                self.advance();
+            } else if c == '|' && self.peek_char() == Some('#') {
+                depth -= 1;
+                self.advance();
             }
-            result.push(c);
             self.advance();
+            if depth == 0 {
+                return Ok(());
+            }
         }
-        self.advance();
-        result
+        Err(TokenError {
+            kind: TokenErrorKind::UnterminatedComment,
+            err: "Unterminated block comment".to_string(),
+            span: self.span_from(start, line, column),
+        })
     }
 
-    fn read_number(&mut self) -> String {
+    fn span_from(
+        &self,
+        start: usize,
+        line: usize,
+        column: usize,
+    ) -> Span {
+        Span::new(
+            self.source.clone(),
+            start,
+            self.pos,
+            line,
+            column,
+        )
+    }
+
+    fn read_string(&mut self) -> Result<String, TokenError> {
+        let (start, line, column) =
+            (self.pos, self.line, self.column);
         let mut result = String::new();
-        while let Some(c) = self.current_char {
-            if !c.is_numeric() && c != '.' {
-                break;
+        self.advance();
+        loop {
+            match self.current_char {
+                Some('"') => break,
+                Some('\\') => {
+                    result.push_str(&self.read_escape()?)
+                }
+                Some(c) => {
+                    result.push(c);
+                    self.advance();
+                }
+                None => {
+                    return Err(TokenError {
+                        kind: TokenErrorKind::UnterminatedString,
+                        err: "Unterminated string".to_string(),
+                        span: self
+                            .span_from(start, line, column),
+                    })
+                }
             }
-            result.push(c);
+        }
+        self.advance();
+        Ok(result)
+    }
+
+    //  处理字符串中 \ 开头的转义，返回转义后的内容
+    fn read_escape(&mut self) -> Result<String, TokenError> {
+        let (start, line, column) =
+            (self.pos, self.line, self.column);
+        self.advance();
+        let c = match self.current_char {
+            Some(c) => c,
+            None => {
+                return Err(TokenError {
+                    kind: TokenErrorKind::UnterminatedString,
+                    err: "Unterminated string".to_string(),
+                    span: self.span_from(start, line, column),
+                })
+            }
+        };
+        self.advance();
+
+        let escaped = match c {
+            '"' => '"',
+            '\\' => '\\',
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            //  \x41; 形式的十六进制转义
+            'x' => {
+                let digits = self.read_hex_digits(';');
+                self.hex_char(&digits, start, line, column)?
+            }
+            //  \u{1F600} 形式的 unicode 转义
+            'u' if self.current_char == Some('{') => {
+                self.advance();
+                let digits = self.read_hex_digits('}');
+                self.hex_char(&digits, start, line, column)?
+            }
+            //  行尾的 \ 表示续行，忽略换行和下一行开头的空白
+            '\n' => {
+                while let Some(c) = self.current_char {
+                    if c == '\n' || !c.is_whitespace() {
+                        break;
+                    }
+                    self.advance();
+                }
+                return Ok(String::new());
+            }
+            _ => {
+                return Err(TokenError {
+                    kind: TokenErrorKind::InvalidEscape,
+                    err: format!("Unknown escape \\{}", c),
+                    span: self.span_from(start, line, column),
+                })
+            }
+        };
+        Ok(escaped.to_string())
+    }
+
+    //  读取十六进制数字直到 terminator，terminator 本身也被消耗
+    fn read_hex_digits(
+        &mut self,
+        terminator: char,
+    ) -> Option<String> {
+        let mut digits = String::new();
+        while let Some(c) = self.current_char {
             self.advance();
+            if c == terminator {
+                return Some(digits);
+            }
+            if !c.is_ascii_hexdigit() {
+                return None;
+            }
+            digits.push(c);
+        }
+        None
+    }
+
+    fn hex_char(
+        &self,
+        digits: &Option<String>,
+        start: usize,
+        line: usize,
+        column: usize,
+    ) -> Result<char, TokenError> {
+        digits
+            .as_ref()
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(|| TokenError {
+                kind: TokenErrorKind::InvalidEscape,
+                err: "Invalid hex escape".to_string(),
+                span: self.span_from(start, line, column),
+            })
+    }
+
+    //  读入完整的数字字面量，格式错误时返回 TokenError 而不是 panic
+    fn read_number(&mut self) -> Result<Token, TokenError> {
+        let (start, line, column) =
+            (self.pos, self.line, self.column);
+        let text = self.read_symbol();
+        parse_number(&text).map_err(|err| TokenError {
+            kind: TokenErrorKind::InvalidNumber,
+            err,
+            span: self.span_from(start, line, column),
+        })
+    }
+
+    fn read_bool(&mut self) -> Result<Token, TokenError> {
+        let (start, line, column) =
+            (self.pos, self.line, self.column);
+        let text = self.read_symbol();
+        match text.as_str() {
+            "#t" | "#true" => Ok(Token::Bool(true)),
+            "#f" | "#false" => Ok(Token::Bool(false)),
+            _ => Err(TokenError {
+                kind: TokenErrorKind::InvalidLiteral,
+                err: format!("Invalid literal {}", text),
+                span: self.span_from(start, line, column),
+            }),
+        }
+    }
+
+    //  #\a、#\space、#\x3bb
+    fn read_char(&mut self) -> Result<Token, TokenError> {
+        let (start, line, column) =
+            (self.pos, self.line, self.column);
+        self.advance();
+        self.advance();
+
+        //  #\ 后的第一个字符总是字符本身，哪怕是 ( 或空白
+        let mut name = match self.current_char {
+            Some(c) => c.to_string(),
+            None => {
+                return Err(TokenError {
+                    kind: TokenErrorKind::InvalidLiteral,
+                    err: "Expected character after #\\"
+                        .to_string(),
+                    span: self.span_from(start, line, column),
+                })
+            }
+        };
+        self.advance();
+        name.push_str(&self.read_symbol());
+
+        char_from_name(&name).map(Token::Char).ok_or_else(|| {
+            TokenError {
+                kind: TokenErrorKind::InvalidLiteral,
+                err: format!("Unknown character #\\{}", name),
+                span: self.span_from(start, line, column),
+            }
+        })
+    }
+
+    //  +5、-.5、+inf.0 这类以符号开头的数字
+    fn starts_signed_number(&self, sign: char) -> bool {
+        let mut rest = self.input.clone();
+        match rest.next() {
+            Some(c) if c.is_ascii_digit() => true,
+            Some('.') => {
+                matches!(rest.next(), Some(c) if c.is_ascii_digit())
+            }
+            Some('i') | Some('n') => {
+                let text: String = std::iter::once(sign)
+                    .chain(self.input.clone().take(5))
+                    .collect();
+                SPECIAL_FLOATS
+                    .iter()
+                    .any(|(name, _)| *name == text)
+                    && !matches!(
+                        self.input.clone().nth(5),
+                        Some(c) if !is_delimiter(c)
+                    )
+            }
+            _ => false,
         }
-        result
     }
 
     fn read_symbol(&mut self) -> String {
         let mut result = String::new();
         while let Some(c) = self.current_char {
-            if c.is_whitespace()
-                || c == '('
-                || c == ')'
-                || c == '\''
+            if is_delimiter(c)
+                || (c == '#' && self.peek_char() == Some('|'))
             {
                 break;
             }
@@ -103,50 +391,309 @@ impl Tokenizer<'_> {
         result
     }
 
-    pub fn next_token(&mut self) -> Option<Token> {
-        self.eat_whitespace();
-        match self.current_char? {
+    pub fn next_token(
+        &mut self,
+    ) -> Result<Option<SpannedToken>, TokenError> {
+        self.eat_whitespace()?;
+        let (start, line, column) =
+            (self.pos, self.line, self.column);
+        let token = match self.read_token()? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        let span = self.span_from(start, line, column);
+        Ok(Some(SpannedToken { token, span }))
+    }
+
+    fn read_token(
+        &mut self,
+    ) -> Result<Option<Token>, TokenError> {
+        let c = match self.current_char {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let token = match c {
             '(' => {
                 self.advance();
-                Some(Token::LParen)
+                Token::LParen
             }
             ')' => {
                 self.advance();
-                Some(Token::RParen)
+                Token::RParen
+            }
+            '{' => {
+                self.advance();
+                Token::LBrace
             }
-            '"' => Some(Token::String(self.read_string())),
-            c if c.is_numeric() => {
-                let val = self.read_number();
-                if val.contains('.') {
-                    Some(Token::Float(val.parse().unwrap()))
+            '}' => {
+                self.advance();
+                Token::RBrace
+            }
+            '\'' => {
+                self.advance();
+                Token::Quote
+            }
+            '`' => {
+                self.advance();
+                Token::Quasiquote
+            }
+            ',' => {
+                self.advance();
+                if self.current_char == Some('@') {
+                    self.advance();
+                    Token::UnquoteSplicing
                 } else {
-                    Some(Token::Integer(val.parse().unwrap()))
+                    Token::Unquote
                 }
             }
+            '#' if self.peek_char() == Some(';') => {
+                self.advance();
+                self.advance();
+                Token::DatumComment
+            }
+            '#' if self.peek_char() == Some('(') => {
+                self.advance();
+                self.advance();
+                Token::VectorOpen
+            }
+            '#' if self.peek_char() == Some('\\') => {
+                self.read_char()?
+            }
+            //  #!optional 等参数列表中的标记，作为普通的 symbol
+            '#' if self.peek_char() == Some('!') => {
+                Token::Symbol(Symbol::intern(
+                    &self.read_symbol(),
+                ))
+            }
+            '#' if matches!(
+                self.peek_char(),
+                Some('t' | 'f')
+            ) =>
+            {
+                self.read_bool()?
+            }
+            '"' => Token::String(self.read_string()?),
+            c if c.is_ascii_digit() => self.read_number()?,
+            '.' if matches!(
+                self.peek_char(),
+                Some(c) if c.is_ascii_digit()
+            ) =>
+            {
+                self.read_number()?
+            }
+            '.' if self.peek_char().is_none_or(is_delimiter) => {
+                self.advance();
+                Token::Dot
+            }
+            '+' | '-' if self.starts_signed_number(c) => {
+                self.read_number()?
+            }
+            '#' if matches!(
+                self.peek_char(),
+                Some(c) if "xXbBoOdD".contains(c)
+            ) =>
+            {
+                self.read_number()?
+            }
+            //  define、car、+ 等都只是普通的 symbol，由 eval 决定含义
             c if c.is_alphabetic()
-                || self.binary_ops.contains(&c) =>
+                || "+-*/%<>=|&!$:?^_~".contains(c) =>
             {
                 let sym = self.read_symbol();
-                if self.keywords.contains(sym.as_str()) {
-                    Some(Token::Keyword(sym))
-                } else if self
-                    .binary_ops
-                    .contains(&sym.chars().next().unwrap())
+                if sym == "true" || sym == "false" {
+                    Token::Bool(sym == "true")
+                } else if sym == "nil" {
+                    Token::Nil
+                } else if let Some(name) = sym
+                    .strip_prefix(':')
+                    .filter(|n| !n.is_empty())
                 {
-                    Some(Token::BinaryOp(sym))
+                    Token::Keyword(Symbol::intern(name))
                 } else {
-                    Some(Token::Symbol(sym))
+                    Token::Symbol(Symbol::intern(&sym))
                 }
             }
-            _ => None,
+            _ => {
+                let (start, line, column) =
+                    (self.pos, self.line, self.column);
+                self.advance();
+                return Err(TokenError {
+                    kind: TokenErrorKind::UnexpectedChar,
+                    err: format!("Unexpected character {:?}", c),
+                    span: self.span_from(start, line, column),
+                });
+            }
+        };
+        Ok(Some(token))
+    }
+}
+
+const SPECIAL_FLOATS: [(&str, f64); 4] = [
+    ("+inf.0", f64::INFINITY),
+    ("-inf.0", f64::NEG_INFINITY),
+    ("+nan.0", f64::NAN),
+    ("-nan.0", f64::NAN),
+];
+
+pub const CHAR_NAMES: [(&str, char); 10] = [
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("null", '\0'),
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("delete", '\u{7f}'),
+    ("escape", '\u{1b}'),
+    ("nul", '\0'),
+];
+
+fn char_from_name(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+    if let Some((_, c)) =
+        CHAR_NAMES.iter().find(|(n, _)| *n == name)
+    {
+        return Some(*c);
+    }
+    name.strip_prefix('x')
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .and_then(char::from_u32)
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "(){}'`,\";".contains(c)
+}
+
+//  支持符号、小数、科学计数法、#x/#b/#o/#d 前缀和 _ 分隔符
+fn parse_number(text: &str) -> Result<Token, String> {
+    let invalid = || format!("Invalid number literal {}", text);
+
+    if let Some((_, f)) =
+        SPECIAL_FLOATS.iter().find(|(name, _)| *name == text)
+    {
+        return Ok(Token::Float(*f));
+    }
+
+    let (radix, body) = match text.get(..2) {
+        Some("#x" | "#X") => (16, &text[2..]),
+        Some("#b" | "#B") => (2, &text[2..]),
+        Some("#o" | "#O") => (8, &text[2..]),
+        Some("#d" | "#D") => (10, &text[2..]),
+        _ => (10, text),
+    };
+
+    let (sign, unsigned) = match body.strip_prefix(['+', '-']) {
+        Some(rest) => (&body[..1], rest),
+        None => ("", body),
+    };
+    let digits =
+        strip_separators(unsigned, radix).ok_or_else(invalid)?;
+
+    let is_integer = digits.chars().all(|c| c.is_digit(radix));
+    if is_integer && !digits.is_empty() {
+        let digits = format!("{}{}", sign, digits);
+        if let Ok(n) = i64::from_str_radix(&digits, radix) {
+            return Ok(Token::Integer(n));
+        }
+        return BigInt::parse_bytes(digits.as_bytes(), radix)
+            .map(Token::BigInt)
+            .ok_or_else(invalid);
+    }
+
+    //  n/d，分母不能有符号，也不能为 0
+    if let Some((numer, denom)) = digits.split_once('/') {
+        let parse = |digits: &str| {
+            if digits.is_empty()
+                || !digits.chars().all(|c| c.is_digit(radix))
+            {
+                return None;
+            }
+            BigInt::parse_bytes(digits.as_bytes(), radix)
+        };
+        let numer = parse(numer).ok_or_else(invalid)?;
+        let denom = parse(denom).ok_or_else(invalid)?;
+        if denom == BigInt::from(0) {
+            return Err(format!(
+                "Zero denominator in number literal {}",
+                text
+            ));
         }
+        let numer = if sign == "-" { -numer } else { numer };
+        return Ok(Token::Rational(BigRational::new(
+            numer, denom,
+        )));
+    }
+
+    if radix != 10 || !is_decimal_float(&digits) {
+        return Err(invalid());
     }
+    format!("{}{}", sign, digits)
+        .parse()
+        .map(Token::Float)
+        .map_err(|_| invalid())
 }
 
-pub fn tokenize(input: &str) -> Result<Vec<Token>, TokenError> {
+//  去掉数字之间的 _，_ 只能出现在两个数字之间
+fn strip_separators(text: &str, radix: u32) -> Option<String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut result = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if *c != '_' {
+            result.push(*c);
+            continue;
+        }
+        let around = (
+            i.checked_sub(1).map(|j| chars[j]),
+            chars.get(i + 1),
+        );
+        match around {
+            (Some(l), Some(r))
+                if l.is_digit(radix) && r.is_digit(radix) => {}
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+//  digits* [. digits*] [e [+-] digits+]，尾数至少有一位数字
+fn is_decimal_float(text: &str) -> bool {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+
+    let (int_part, frac_part) = match mantissa.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, frac_part),
+        None => (mantissa, ""),
+    };
+    let all_digits =
+        |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if !all_digits(int_part)
+        || !all_digits(frac_part)
+        || int_part.len() + frac_part.len() == 0
+    {
+        return false;
+    }
+
+    match exponent {
+        Some(exp) => {
+            let exp =
+                exp.strip_prefix(['+', '-']).unwrap_or(exp);
+            !exp.is_empty() && all_digits(exp)
+        }
+        None => true,
+    }
+}
+
+pub fn tokenize(
+    input: &str,
+) -> Result<Vec<SpannedToken>, TokenError> {
     let mut tokenizer = Tokenizer::new(input);
     let mut tokens = vec![];
-    while let Some(token) = tokenizer.next_token() {
+    while let Some(token) = tokenizer.next_token()? {
         tokens.push(token);
     }
 
@@ -157,15 +704,23 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, TokenError> {
 mod tests {
     use super::*;
 
+    fn tokens_of(program: &str) -> Vec<Token> {
+        tokenize(program)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.token)
+            .collect()
+    }
+
     #[test]
     fn test_add() {
         let program = "(+ 1 2)";
-        let tokens = tokenize(&program).unwrap_or(vec![]);
+        let tokens = tokens_of(program);
         assert_eq!(
             tokens,
             vec![
                 Token::LParen,
-                Token::BinaryOp("+".to_string()),
+                Token::Symbol(Symbol::intern("+")),
                 Token::Integer(1),
                 Token::Integer(2),
                 Token::RParen,
@@ -182,32 +737,418 @@ mod tests {
                   (* pi (* r r))
               )
           ";
-        let tokens = tokenize(program).unwrap_or(vec![]);
+        let tokens = tokens_of(program);
         assert_eq!(
             tokens,
             vec![
                 Token::LParen,
                 Token::LParen,
-                Token::Keyword("define".to_string()),
-                Token::Symbol("r".to_string()),
+                Token::Symbol(Symbol::intern("define")),
+                Token::Symbol(Symbol::intern("r")),
                 Token::Integer(10),
                 Token::RParen,
                 Token::LParen,
-                Token::Keyword("define".to_string()),
-                Token::Symbol("pi".to_string()),
+                Token::Symbol(Symbol::intern("define")),
+                Token::Symbol(Symbol::intern("pi")),
                 Token::Integer(314),
                 Token::RParen,
                 Token::LParen,
-                Token::BinaryOp("*".to_string()),
-                Token::Symbol("pi".to_string()),
+                Token::Symbol(Symbol::intern("*")),
+                Token::Symbol(Symbol::intern("pi")),
                 Token::LParen,
-                Token::BinaryOp("*".to_string()),
-                Token::Symbol("r".to_string()),
-                Token::Symbol("r".to_string()),
+                Token::Symbol(Symbol::intern("*")),
+                Token::Symbol(Symbol::intern("r")),
+                Token::Symbol(Symbol::intern("r")),
                 Token::RParen,
                 Token::RParen,
                 Token::RParen
             ]
         );
     }
+
+    #[test]
+    fn test_token_spans() {
+        let program = "(define r 10)\n  (* r \"圆\")";
+        let tokens = tokenize(program).unwrap();
+        let positions = tokens
+            .iter()
+            .map(|t| (t.span.line, t.span.column, t.span.text()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            vec![
+                (1, 1, "("),
+                (1, 2, "define"),
+                (1, 9, "r"),
+                (1, 11, "10"),
+                (1, 13, ")"),
+                (2, 3, "("),
+                (2, 4, "*"),
+                (2, 6, "r"),
+                (2, 8, "\"圆\""),
+                (2, 11, ")"),
+            ]
+        );
+        assert_eq!(tokens[8].span.start, 21);
+        assert_eq!(tokens[8].span.end, 26);
+    }
+
+    #[test]
+    fn test_quote_tokens() {
+        let tokens = tokens_of("'(a `(b ,c ,@d))");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Quote,
+                Token::LParen,
+                Token::Symbol(Symbol::intern("a")),
+                Token::Quasiquote,
+                Token::LParen,
+                Token::Symbol(Symbol::intern("b")),
+                Token::Unquote,
+                Token::Symbol(Symbol::intern("c")),
+                Token::UnquoteSplicing,
+                Token::Symbol(Symbol::intern("d")),
+                Token::RParen,
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_comment() {
+        let program = "
+            ; area of a circle
+            (* pi ; constant
+               r) ; trailing
+            ;; no newline at the end";
+        let tokens = tokens_of(program);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol(Symbol::intern("*")),
+                Token::Symbol(Symbol::intern("pi")),
+                Token::Symbol(Symbol::intern("r")),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_comment() {
+        let program = "#| header |#(+#| a #| nested |# b |#1
+            #|
+              multi-line
+            |#2)#||#";
+        let tokens = tokens_of(program);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol(Symbol::intern("+")),
+                Token::Integer(1),
+                Token::Integer(2),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_datum_comment_token() {
+        let tokens = tokens_of("(a #;(b c) d)");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol(Symbol::intern("a")),
+                Token::DatumComment,
+                Token::LParen,
+                Token::Symbol(Symbol::intern("b")),
+                Token::Symbol(Symbol::intern("c")),
+                Token::RParen,
+                Token::Symbol(Symbol::intern("d")),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let program =
+            r#""say \"hi\"\\ \n\t\r\0 \x41;\x3bb; \u{1F600}""#;
+        let tokens = tokens_of(program);
+        assert_eq!(
+            tokens,
+            vec![Token::String(
+                "say \"hi\"\\ \n\t\r\0 Aλ 😀".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_multi_line_string() {
+        let program =
+            "\"line one\nline two \\\n      continued\"";
+        let tokens = tokens_of(program);
+        assert_eq!(
+            tokens,
+            vec![Token::String(
+                "line one\nline two continued".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let err = tokenize("(print\n  \"hello)").unwrap_err();
+        assert_eq!(err.err, "Unterminated string");
+        assert_eq!((err.span.line, err.span.column), (2, 3));
+        assert_eq!(err.span.text(), "\"hello)");
+    }
+
+    #[test]
+    fn test_invalid_escape() {
+        let err = tokenize(r#""a\qb""#).unwrap_err();
+        assert_eq!(err.err, "Unknown escape \\q");
+        assert_eq!(err.span.text(), "\\q");
+
+        let err = tokenize(r#""\xZZ;""#).unwrap_err();
+        assert_eq!(err.err, "Invalid hex escape");
+        let err = tokenize(r#""\u{110000}""#).unwrap_err();
+        assert_eq!(err.err, "Invalid hex escape");
+    }
+
+    #[test]
+    fn test_number_literals() {
+        let tokens = tokens_of(
+            "-5 +7 1e10 1.5e-3 -2.5E+2 .5 -.25 5. 1_000_000 #xFF \
+             #x-1f #b1010 #o17 #d99 #xdead_beef",
+        );
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Integer(-5),
+                Token::Integer(7),
+                Token::Float(1e10),
+                Token::Float(1.5e-3),
+                Token::Float(-250.0),
+                Token::Float(0.5),
+                Token::Float(-0.25),
+                Token::Float(5.0),
+                Token::Integer(1_000_000),
+                Token::Integer(255),
+                Token::Integer(-31),
+                Token::Integer(10),
+                Token::Integer(15),
+                Token::Integer(99),
+                Token::Integer(0xdead_beef),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rational_literals() {
+        let tokens = tokens_of("1/3 -6/4 #x1/a 4/2");
+        let rational = |numer: i64, denom: i64| {
+            Token::Rational(BigRational::new(
+                BigInt::from(numer),
+                BigInt::from(denom),
+            ))
+        };
+        assert_eq!(
+            tokens,
+            vec![
+                rational(1, 3),
+                rational(-3, 2),
+                rational(1, 10),
+                rational(2, 1),
+            ]
+        );
+
+        for text in ["1/", "1/-2", "1/2/3", "1.5/2"] {
+            let err = tokenize(text).unwrap_err();
+            assert_eq!(err.kind, TokenErrorKind::InvalidNumber);
+        }
+        let err = tokenize("1/0").unwrap_err();
+        assert_eq!(
+            err.err,
+            "Zero denominator in number literal 1/0"
+        );
+    }
+
+    #[test]
+    fn test_special_floats() {
+        let tokens = tokens_of("+inf.0 -inf.0 +nan.0");
+        assert_eq!(tokens[0], Token::Float(f64::INFINITY));
+        assert_eq!(tokens[1], Token::Float(f64::NEG_INFINITY));
+        match tokens[2] {
+            Token::Float(f) => assert!(f.is_nan()),
+            _ => panic!("expected a float"),
+        }
+    }
+
+    #[test]
+    fn test_signs_are_still_symbols() {
+        let tokens = tokens_of("(- 5 -x) (+ -) -inf.x");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol(Symbol::intern("-")),
+                Token::Integer(5),
+                Token::Symbol(Symbol::intern("-x")),
+                Token::RParen,
+                Token::LParen,
+                Token::Symbol(Symbol::intern("+")),
+                Token::Symbol(Symbol::intern("-")),
+                Token::RParen,
+                Token::Symbol(Symbol::intern("-inf.x")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        for (program, text) in [
+            ("(+ 1.2.3 1)", "1.2.3"),
+            ("12abc", "12abc"),
+            ("1e", "1e"),
+            ("1__0", "1__0"),
+            ("1_", "1_"),
+            ("#b102", "#b102"),
+            ("#x1.5", "#x1.5"),
+        ] {
+            let err = tokenize(program).unwrap_err();
+            assert_eq!(
+                err.err,
+                format!("Invalid number literal {}", text)
+            );
+            assert_eq!(err.span.text(), text);
+        }
+    }
+
+    #[test]
+    fn test_big_integer_literals() {
+        let tokens = tokens_of(
+            "99999999999999999999 -9223372036854775808 #x1_0000_0000_0000_0000",
+        );
+        assert_eq!(
+            tokens,
+            vec![
+                Token::BigInt(
+                    "99999999999999999999".parse().unwrap()
+                ),
+                Token::Integer(i64::MIN),
+                Token::BigInt(BigInt::from(u64::MAX) + 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bool_and_nil_literals() {
+        let tokens =
+            tokens_of("#t #f #true #false true false nil");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Bool(true),
+                Token::Bool(false),
+                Token::Bool(true),
+                Token::Bool(false),
+                Token::Bool(true),
+                Token::Bool(false),
+                Token::Nil,
+            ]
+        );
+
+        let err = tokenize("#tru").unwrap_err();
+        assert_eq!(err.err, "Invalid literal #tru");
+    }
+
+    #[test]
+    fn test_keywords() {
+        let tokens = tokens_of("(f :port 8080 : a:b)");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol(Symbol::intern("f")),
+                Token::Keyword(Symbol::intern("port")),
+                Token::Integer(8080),
+                Token::Symbol(Symbol::intern(":")),
+                Token::Symbol(Symbol::intern("a:b")),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let tokens = tokens_of(
+            r"(#\a #\Z #\space #\newline #\x3bb #\x #\( #\))",
+        );
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Char('a'),
+                Token::Char('Z'),
+                Token::Char(' '),
+                Token::Char('\n'),
+                Token::Char('λ'),
+                Token::Char('x'),
+                Token::Char('('),
+                Token::Char(')'),
+                Token::RParen,
+            ]
+        );
+
+        let err = tokenize(r"#\spaces").unwrap_err();
+        assert_eq!(err.err, r"Unknown character #\spaces");
+        assert_eq!(err.span.text(), r"#\spaces");
+    }
+
+    #[test]
+    fn test_dot() {
+        let tokens = tokens_of("(1 . 2) (a .5) (b.c)");
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Integer(1),
+                Token::Dot,
+                Token::Integer(2),
+                Token::RParen,
+                Token::LParen,
+                Token::Symbol(Symbol::intern("a")),
+                Token::Float(0.5),
+                Token::RParen,
+                Token::LParen,
+                Token::Symbol(Symbol::intern("b.c")),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unexpected_character() {
+        let err = tokenize("(foo\n  [bar])").unwrap_err();
+        assert_eq!(err.kind, TokenErrorKind::UnexpectedChar);
+        assert_eq!(err.err, "Unexpected character '['");
+        assert_eq!((err.span.line, err.span.column), (2, 3));
+
+        let tokens = tokens_of("(?x !y $z _w ~v ^u)");
+        assert_eq!(tokens.len(), 8);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let err = tokenize("(a #| never closed").unwrap_err();
+        assert_eq!(
+            err.kind,
+            TokenErrorKind::UnterminatedComment
+        );
+        assert_eq!((err.span.line, err.span.column), (1, 4));
+    }
 }
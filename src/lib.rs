@@ -1,6 +1,11 @@
 pub mod env;
 pub mod eval;
 pub mod object;
+pub mod parser;
+pub mod span;
+pub mod symbol;
 
+mod builtins;
 mod lexer;
-mod parser;
+mod number;
+mod stack;
@@ -1,30 +1,46 @@
 use rlisp::env;
 use rlisp::eval;
 use rlisp::object::Object;
+use rlisp::parser::{ReadStatus, Reader};
 
 use linefeed::{Interface, ReadResult};
 use std::cell::RefCell;
 use std::rc::Rc;
 
 const PROMPT: &str = "lisp-rs> ";
+const CONTINUATION_PROMPT: &str = "     ... ";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let reader = Interface::new(PROMPT).unwrap();
-    reader.set_prompt(PROMPT).unwrap();
+    let interface = Interface::new(PROMPT).unwrap();
+    interface.set_prompt(PROMPT).unwrap();
     let env = Rc::new(RefCell::new(env::Env::new()));
+    let mut reader = Reader::new();
 
     while let ReadResult::Input(input) =
-        reader.read_line().unwrap()
+        interface.read_line().unwrap()
     {
-        if input.eq("exit") {
+        if reader.is_empty() && input.eq("exit") {
             break;
         }
 
-        let val = eval::eval(input.as_ref(), env.clone())?;
-        match val {
-            Object::Void => {}
-            _ => println!("{}", val),
-        };
+        match reader.feed(&input) {
+            //  form 未结束，继续读下一行
+            Ok(ReadStatus::Incomplete) => {
+                interface
+                    .set_prompt(CONTINUATION_PROMPT)
+                    .unwrap();
+                continue;
+            }
+            Ok(ReadStatus::Complete(source)) => {
+                match eval::eval_program(&source, env.clone()) {
+                    Ok(Object::Void) => {}
+                    Ok(val) => println!("{}", val),
+                    Err(err) => eprintln!("{}", err),
+                };
+            }
+            Err(err) => eprintln!("{}", err),
+        }
+        interface.set_prompt(PROMPT).unwrap();
     }
 
     println!("Goodbye!");
@@ -0,0 +1,391 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, ToPrimitive, Zero};
+
+use crate::object::Object;
+
+//  参与运算的数字，运算前两边提升到同一层级：
+//  Integer < BigInt < Rational < Float，只有 Float 是不精确的
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    BigInt(BigInt),
+    Rational(BigRational),
+    Float(f64),
+}
+
+//  提升到同一层级后的两个操作数
+enum Operands {
+    Integer(i64, i64),
+    BigInt(BigInt, BigInt),
+    Rational(BigRational, BigRational),
+    Float(f64, f64),
+}
+
+impl Number {
+    pub fn from_object(obj: &Object) -> Option<Number> {
+        match obj {
+            Object::Integer(n) => Some(Number::Integer(*n)),
+            Object::BigInt(n) => Some(Number::BigInt(n.clone())),
+            Object::Rational(n) => {
+                Some(Number::Rational(n.clone()))
+            }
+            Object::Float(n) => Some(Number::Float(*n)),
+            _ => None,
+        }
+    }
+
+    //  分母为 1 的 Rational 变为整数，在 i64 范围内的 BigInt 降级为 Integer
+    pub fn into_object(self) -> Object {
+        match self {
+            Number::Integer(n) => Object::Integer(n),
+            Number::BigInt(n) => match n.to_i64() {
+                Some(n) => Object::Integer(n),
+                None => Object::BigInt(n),
+            },
+            Number::Rational(n) if n.is_integer() => {
+                Number::BigInt(n.to_integer()).into_object()
+            }
+            Number::Rational(n) => Object::Rational(n),
+            Number::Float(n) => Object::Float(n),
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Number::Float(_))
+    }
+
+    //  整数，包括没有小数部分的浮点数
+    pub fn is_integer(&self) -> bool {
+        match self {
+            Number::Integer(_) | Number::BigInt(_) => true,
+            Number::Rational(n) => n.is_integer(),
+            Number::Float(n) => n.fract() == 0.0,
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            Number::Integer(n) => *n as f64,
+            Number::BigInt(n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Rational(n) => {
+                n.to_f64().unwrap_or(f64::NAN)
+            }
+            Number::Float(n) => *n,
+        }
+    }
+
+    //  Float 的值需要先转为精确数，inf 和 NaN 没有对应的精确数
+    fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Number::Integer(n) => {
+                Some(BigRational::from_integer(BigInt::from(*n)))
+            }
+            Number::BigInt(n) => {
+                Some(BigRational::from_integer(n.clone()))
+            }
+            Number::Rational(n) => Some(n.clone()),
+            Number::Float(n) => BigRational::from_f64(*n),
+        }
+    }
+
+    pub fn to_inexact(&self) -> Number {
+        Number::Float(self.to_f64())
+    }
+
+    pub fn to_exact(&self) -> Option<Number> {
+        self.to_rational().map(Number::Rational)
+    }
+
+    pub fn numerator(&self) -> Option<Number> {
+        let n = self.to_rational()?;
+        self.with_exactness(Number::BigInt(n.numer().clone()))
+    }
+
+    pub fn denominator(&self) -> Option<Number> {
+        let n = self.to_rational()?;
+        self.with_exactness(Number::BigInt(n.denom().clone()))
+    }
+
+    //  结果与 self 的精确性一致
+    fn with_exactness(&self, n: Number) -> Option<Number> {
+        match self {
+            Number::Float(_) => Some(n.to_inexact()),
+            _ => Some(n),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(n) => *n == 0,
+            Number::BigInt(n) => n.is_zero(),
+            Number::Rational(n) => n.is_zero(),
+            Number::Float(n) => *n == 0.0,
+        }
+    }
+}
+
+fn promote(left: &Number, right: &Number) -> Operands {
+    match (left, right) {
+        (Number::Integer(l), Number::Integer(r)) => {
+            Operands::Integer(*l, *r)
+        }
+        (Number::Float(_), _) | (_, Number::Float(_)) => {
+            Operands::Float(left.to_f64(), right.to_f64())
+        }
+        (Number::Rational(_), _) | (_, Number::Rational(_)) => {
+            Operands::Rational(
+                left.to_rational().unwrap_or_default(),
+                right.to_rational().unwrap_or_default(),
+            )
+        }
+        (Number::BigInt(l), Number::BigInt(r)) => {
+            Operands::BigInt(l.clone(), r.clone())
+        }
+        (Number::Integer(l), Number::BigInt(r)) => {
+            Operands::BigInt(BigInt::from(*l), r.clone())
+        }
+        (Number::BigInt(l), Number::Integer(r)) => {
+            Operands::BigInt(l.clone(), BigInt::from(*r))
+        }
+    }
+}
+
+//  i64 运算溢出时改用 BigInt 重新计算
+fn arith(
+    left: &Number,
+    right: &Number,
+    int: fn(i64, i64) -> Option<i64>,
+    big: fn(BigInt, BigInt) -> BigInt,
+    rational: fn(BigRational, BigRational) -> BigRational,
+    float: fn(f64, f64) -> f64,
+) -> Number {
+    match promote(left, right) {
+        Operands::Integer(l, r) => match int(l, r) {
+            Some(n) => Number::Integer(n),
+            None => Number::BigInt(big(
+                BigInt::from(l),
+                BigInt::from(r),
+            )),
+        },
+        Operands::BigInt(l, r) => Number::BigInt(big(l, r)),
+        Operands::Rational(l, r) => {
+            Number::Rational(rational(l, r))
+        }
+        Operands::Float(l, r) => Number::Float(float(l, r)),
+    }
+}
+
+pub fn add(left: &Number, right: &Number) -> Number {
+    arith(
+        left,
+        right,
+        i64::checked_add,
+        |l, r| l + r,
+        |l, r| l + r,
+        |l, r| l + r,
+    )
+}
+
+pub fn sub(left: &Number, right: &Number) -> Number {
+    arith(
+        left,
+        right,
+        i64::checked_sub,
+        |l, r| l - r,
+        |l, r| l - r,
+        |l, r| l - r,
+    )
+}
+
+pub fn mul(left: &Number, right: &Number) -> Number {
+    arith(
+        left,
+        right,
+        i64::checked_mul,
+        |l, r| l * r,
+        |l, r| l * r,
+        |l, r| l * r,
+    )
+}
+
+//  精确数除以 0 没有结果，浮点数除以 0 得到 inf 或 NaN
+fn divides_by_zero(left: &Number, right: &Number) -> bool {
+    right.is_zero() && left.is_exact() && right.is_exact()
+}
+
+//  精确数相除的结果是精确的，不能整除时得到 Rational
+pub fn div(left: &Number, right: &Number) -> Option<Number> {
+    if divides_by_zero(left, right) {
+        return None;
+    }
+    let result = match promote(left, right) {
+        Operands::Float(l, r) => Number::Float(l / r),
+        Operands::Integer(l, r)
+            if l.checked_rem(r) == Some(0) =>
+        {
+            Number::Integer(l / r)
+        }
+        _ => Number::Rational(
+            left.to_rational()? / right.to_rational()?,
+        ),
+    };
+    Some(result)
+}
+
+//  向零取整的商
+pub fn quotient(
+    left: &Number,
+    right: &Number,
+) -> Option<Number> {
+    if divides_by_zero(left, right) {
+        return None;
+    }
+    Some(arith(
+        left,
+        right,
+        i64::checked_div,
+        |l, r| l / r,
+        |l, r| (l / r).trunc(),
+        |l, r| (l / r).trunc(),
+    ))
+}
+
+//  符号与被除数相同的余数
+pub fn remainder(
+    left: &Number,
+    right: &Number,
+) -> Option<Number> {
+    if divides_by_zero(left, right) {
+        return None;
+    }
+    Some(arith(
+        left,
+        right,
+        i64::checked_rem,
+        |l, r| l % r,
+        |l, r| l % r,
+        |l, r| l % r,
+    ))
+}
+
+//  符号与除数相同的余数
+pub fn modulo(left: &Number, right: &Number) -> Option<Number> {
+    if divides_by_zero(left, right) {
+        return None;
+    }
+    Some(arith(
+        left,
+        right,
+        |l, r| {
+            l.checked_rem(r).map(|m| {
+                if m != 0 && (m < 0) != (r < 0) {
+                    m + r
+                } else {
+                    m
+                }
+            })
+        },
+        |l, r| l.mod_floor(&r),
+        |l, r| l.clone() - r.clone() * (l / r).floor(),
+        |l, r| l - r * (l / r).floor(),
+    ))
+}
+
+//  有 NaN 参与时无法比较
+pub fn compare(
+    left: &Number,
+    right: &Number,
+) -> Option<Ordering> {
+    match promote(left, right) {
+        Operands::Integer(l, r) => Some(l.cmp(&r)),
+        Operands::BigInt(l, r) => Some(l.cmp(&r)),
+        Operands::Rational(l, r) => Some(l.cmp(&r)),
+        Operands::Float(l, r) => l.partial_cmp(&r),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rational(numer: i64, denom: i64) -> Number {
+        Number::Rational(BigRational::new(
+            BigInt::from(numer),
+            BigInt::from(denom),
+        ))
+    }
+
+    #[test]
+    fn test_promote_on_overflow() {
+        let max = Number::Integer(i64::MAX);
+        let sum = add(&max, &Number::Integer(1));
+        assert_eq!(
+            sum,
+            Number::BigInt(BigInt::from(i64::MAX) + 1)
+        );
+
+        let back = sub(&sum, &Number::Integer(1)).into_object();
+        assert_eq!(back, Object::Integer(i64::MAX));
+
+        let min = Number::Integer(i64::MIN);
+        let quotient = div(&min, &Number::Integer(-1)).unwrap();
+        assert_eq!(
+            quotient.into_object().to_string(),
+            "9223372036854775808"
+        );
+        assert_eq!(div(&min, &Number::Integer(0)), None);
+    }
+
+    #[test]
+    fn test_rational_contagion() {
+        let third =
+            div(&Number::Integer(1), &Number::Integer(3));
+        assert_eq!(third, Some(rational(1, 3)));
+
+        let sum = add(&rational(1, 3), &rational(2, 3));
+        assert_eq!(sum.into_object(), Object::Integer(1));
+
+        let sum = add(&rational(1, 2), &Number::Float(0.25));
+        assert_eq!(sum, Number::Float(0.75));
+
+        assert_eq!(
+            compare(&rational(1, 3), &Number::Integer(0)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(&rational(1, 2), &Number::Float(0.5)),
+            Some(Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn test_integer_division() {
+        let cases = [
+            (7, 2, 3, 1, 1),
+            (-7, 2, -3, -1, 1),
+            (7, -2, -3, 1, -1),
+            (-7, -2, 3, -1, -1),
+        ];
+        for (l, r, q, rem, m) in cases {
+            let (l, r) =
+                (Number::Integer(l), Number::Integer(r));
+            assert_eq!(
+                quotient(&l, &r),
+                Some(Number::Integer(q))
+            );
+            assert_eq!(
+                remainder(&l, &r),
+                Some(Number::Integer(rem))
+            );
+            assert_eq!(modulo(&l, &r), Some(Number::Integer(m)));
+        }
+
+        let m =
+            modulo(&Number::Float(-7.0), &Number::Integer(2));
+        assert_eq!(m, Some(Number::Float(1.0)));
+    }
+}
@@ -1,64 +1,703 @@
-use std::{cell::RefCell, fmt, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+    rc::Rc,
+};
+
+use indexmap::IndexMap;
+use num_bigint::BigInt;
+use num_rational::BigRational;
 
 use crate::env::Env;
+use crate::eval::EvalError;
+use crate::lexer::CHAR_NAMES;
+use crate::span::Span;
+use crate::stack;
+use crate::symbol::Symbol;
+
+//  内置函数接收已求值的参数和调用处的 env，参数个数在调用前检查
+pub type BuiltinFn = fn(
+    &[Object],
+    &Rc<RefCell<Env>>,
+) -> Result<Object, EvalError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    //  包含两端
+    Range(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, n: usize) -> bool {
+        match *self {
+            Arity::Exact(arity) => n == arity,
+            Arity::AtLeast(arity) => n >= arity,
+            Arity::Range(min, max) => min <= n && n <= max,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+            Arity::Range(min, max) => {
+                write!(f, "{} to {}", min, max)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub func: BuiltinFn,
+}
+
+impl Builtin {
+    pub const fn new(
+        name: &'static str,
+        arity: Arity,
+        func: BuiltinFn,
+    ) -> Self {
+        Builtin { name, arity, func }
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+//  lambda 创建的过程，define 第一次绑定它时记下名字，出错时用来报告
+#[derive(Debug)]
+pub struct Procedure {
+    pub name: Cell<Option<Symbol>>,
+    pub params: Params,
+    //  body 中依次求值的表达式
+    pub body: Vec<Object>,
+    //  body 在源码中对应的那串 pair，用来查找出错的 atom 的位置
+    pub body_source: Object,
+    //  定义时的 env
+    pub env: Rc<RefCell<Env>>,
+}
+
+impl Procedure {
+    pub fn name(&self) -> Option<&'static str> {
+        self.name.get().map(Symbol::as_str)
+    }
+}
+
+//  lambda 的参数：依次绑定的必需参数，可以省略的可选参数，以及 (a b . rest) 中收集剩余参数的 rest
+//  可选参数写作 (name default) 或者跟在 #!optional 之后，省略时求值默认值，没有默认值则为 Void
+//  #!key 之后是关键字参数，调用时写作 :name value，顺序任意，省略时同样使用默认值
+#[derive(Debug, Default, PartialEq)]
+pub struct Params {
+    pub required: Vec<Symbol>,
+    pub optional: Vec<(Symbol, Option<Object>)>,
+    pub keys: Vec<(Symbol, Option<Object>)>,
+    pub rest: Option<Symbol>,
+}
+
+impl Params {
+    pub fn arity(&self) -> Arity {
+        let min = self.required.len();
+        let max =
+            min + self.optional.len() + 2 * self.keys.len();
+        match self.rest {
+            Some(_) => Arity::AtLeast(min),
+            None if max == min => Arity::Exact(min),
+            None => Arity::Range(min, max),
+        }
+    }
+}
+
+impl fmt::Display for Params {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts: Vec<String> = self
+            .required
+            .iter()
+            .map(|sym| sym.to_string())
+            .collect();
+        let mut marked = false;
+        for (name, default) in &self.optional {
+            match default {
+                Some(default) => {
+                    parts.push(format!("({} {})", name, default))
+                }
+                None => {
+                    if !marked {
+                        parts.push("#!optional".to_string());
+                        marked = true;
+                    }
+                    parts.push(name.to_string());
+                }
+            }
+        }
+        if !self.keys.is_empty() {
+            parts.push("#!key".to_string());
+        }
+        for (name, default) in &self.keys {
+            match default {
+                Some(default) => {
+                    parts.push(format!("({} {})", name, default))
+                }
+                None => parts.push(name.to_string()),
+            }
+        }
+        if let Some(rest) = self.rest {
+            parts.push(format!(". {}", rest));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+//  cons cell，多个 list 可以共享同一个尾部，set-car!/set-cdr! 会修改所有共享者看到的值
+//  代码和数据使用同一种 list，parser 读入的 list 在第一个 pair 上记录源码位置
+//  atom 本身没有位置，由包含它的 pair 在 car_span 中记录
+#[derive(Debug, Clone)]
+pub struct Pair {
+    car: RefCell<Object>,
+    cdr: RefCell<Object>,
+    pub span: Option<Span>,
+    pub car_span: Option<Span>,
+}
+
+impl Pair {
+    pub fn car(&self) -> Object {
+        self.car.borrow().clone()
+    }
+
+    pub fn cdr(&self) -> Object {
+        self.cdr.borrow().clone()
+    }
 
-#[derive(Debug, Clone, PartialEq)]
+    pub fn set_car(&self, value: Object) {
+        *self.car.borrow_mut() = value;
+    }
+
+    pub fn set_cdr(&self, value: Object) {
+        *self.cdr.borrow_mut() = value;
+    }
+}
+
+//  最后一个引用释放时，把 pair 和容器中的子容器拆下来逐个释放
+//  长 list 或者深层嵌套的 pair、向量、box、哈希表递归 drop 会导致栈溢出
+impl Drop for Object {
+    fn drop(&mut self) {
+        let mut pending = vec![];
+        self.take_children(&mut pending);
+        while let Some(mut obj) = pending.pop() {
+            obj.take_children(&mut pending);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Object {
     Void,
-    Keyword(String),
-    BinaryOp(String),
     Integer(i64),
+    //  超出 i64 范围的整数，范围内的值总是用 Integer 表示
+    BigInt(BigInt),
+    //  约分后分母大于 1 的精确分数
+    Rational(BigRational),
     Float(f64),
     Bool(bool),
+    Char(char),
     String(String),
-    Symbol(String),
-    ListData(Vec<Object>),
-    Lambda(Vec<String>, Vec<Object>, Rc<RefCell<Env>>),
-    List(Vec<Object>),
+    Symbol(Symbol),
+    //  :name 关键字，求值为自身，用于关键字参数
+    Keyword(Symbol),
+    //  空 list
+    Nil,
+    Pair(Rc<Pair>),
+    Lambda(Rc<Procedure>),
+    Builtin(Builtin),
+    //  可变的哈希表，多个引用共享同一个表，按插入的顺序遍历和显示
+    HashMap(Rc<RefCell<IndexMap<Object, Object>>>),
+    //  定长可变的向量，多个引用共享同一组元素
+    Vector(Rc<RefCell<Vec<Object>>>),
+    //  只装一个值的可变容器
+    Box(Rc<RefCell<Object>>),
+}
+
+//  span 只是附加信息，不参与相等比较
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut HashSet::new())
+    }
+}
+
+//  NaN 不等于自身，作为 key 时永远找不到，其余值满足 Eq 的要求
+impl Eq for Object {}
+
+//  相等的值哈希值相同；过程和可变容器不能作为 key，只哈希类型
+impl Hash for Object {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Object::Integer(n) => n.hash(state),
+            Object::BigInt(n) => n.hash(state),
+            Object::Rational(n) => n.hash(state),
+            //  0.0 和 -0.0 相等，哈希值也必须相同
+            Object::Float(n) if *n == 0.0 => 0u64.hash(state),
+            Object::Float(n) => n.to_bits().hash(state),
+            Object::Bool(b) => b.hash(state),
+            Object::Char(c) => c.hash(state),
+            Object::String(s) => s.hash(state),
+            Object::Symbol(s) | Object::Keyword(s) => {
+                s.hash(state)
+            }
+            Object::Builtin(builtin) => builtin.name.hash(state),
+            Object::Void
+            | Object::Nil
+            | Object::Lambda(..)
+            | Object::Pair(_)
+            | Object::HashMap(_)
+            | Object::Vector(_)
+            | Object::Box(_) => {}
+        }
+    }
 }
 
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &mut HashSet::new())
+    }
+}
+
+//  正在显示的容器的地址，容器再次出现说明有环
+type Visiting = HashSet<*const ()>;
+
+//  已经开始比较的 (左, 右) 容器的地址
+type Compared = HashSet<(*const (), *const ())>;
+
+impl Object {
+    //  car 方向嵌套很深的 list 和容器递归比较，需要扩展栈空间
+    fn equals(
+        &self,
+        other: &Object,
+        compared: &mut Compared,
+    ) -> bool {
+        if self.compared_before(other, compared) {
+            return true;
+        }
+        stack::maybe_grow(|| self.equals_value(other, compared))
+    }
+
+    //  同一个容器，或者这一对容器已经在比较中
+    //  再次遇到说明两边沿着环回到了原处，环上其余部分由第一次的比较负责
+    fn compared_before(
+        &self,
+        other: &Object,
+        compared: &mut Compared,
+    ) -> bool {
+        match (self.container_ptr(), other.container_ptr()) {
+            (Some(l), Some(r)) => {
+                l == r || !compared.insert((l, r))
+            }
+            _ => false,
+        }
+    }
+
+    fn equals_value(
+        &self,
+        other: &Object,
+        compared: &mut Compared,
+    ) -> bool {
+        match (self, other) {
+            (Object::Void, Object::Void) => true,
+            (Object::Integer(l), Object::Integer(r)) => l == r,
+            (Object::BigInt(l), Object::BigInt(r)) => l == r,
+            (Object::Rational(l), Object::Rational(r)) => l == r,
+            (Object::Float(l), Object::Float(r)) => l == r,
+            (Object::Bool(l), Object::Bool(r)) => l == r,
+            (Object::Char(l), Object::Char(r)) => l == r,
+            (Object::String(l), Object::String(r)) => l == r,
+            (Object::Symbol(l), Object::Symbol(r)) => l == r,
+            (Object::Keyword(l), Object::Keyword(r)) => l == r,
+            (Object::Nil, Object::Nil) => true,
+            (Object::Pair(lp), Object::Pair(rp)) => {
+                //  沿 cdr 迭代比较，避免长 list 递归过深
+                let (mut lp, mut rp) = (lp.clone(), rp.clone());
+                loop {
+                    if !lp
+                        .car
+                        .borrow()
+                        .equals(&rp.car.borrow(), compared)
+                    {
+                        return false;
+                    }
+                    let (l, r) = (lp.cdr(), rp.cdr());
+                    match (&l, &r) {
+                        (Object::Pair(ln), Object::Pair(rn)) => {
+                            if l.compared_before(&r, compared) {
+                                return true;
+                            }
+                            (lp, rp) = (ln.clone(), rn.clone());
+                        }
+                        _ => return l.equals(&r, compared),
+                    }
+                }
+            }
+            //  过程按同一性比较，env 中可能包含过程自身，按值比较不会结束
+            (Object::Lambda(l), Object::Lambda(r)) => {
+                Rc::ptr_eq(l, r)
+            }
+            (Object::Builtin(l), Object::Builtin(r)) => {
+                l.name == r.name
+            }
+            //  key 不会是容器，按 key 查找另一边的值再比较
+            (Object::HashMap(l), Object::HashMap(r)) => {
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len()
+                    && l.iter().all(|(key, l)| {
+                        r.get(key).is_some_and(|r| {
+                            l.equals(r, compared)
+                        })
+                    })
+            }
+            (Object::Vector(l), Object::Vector(r)) => {
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len()
+                    && l.iter()
+                        .zip(r.iter())
+                        .all(|(l, r)| l.equals(r, compared))
+            }
+            (Object::Box(l), Object::Box(r)) => {
+                l.borrow().equals(&r.borrow(), compared)
+            }
+            _ => false,
+        }
+    }
+
+    //  嵌套的 list 和容器递归显示，层数很深时需要扩展栈空间
+    fn write(
+        &self,
+        f: &mut fmt::Formatter,
+        visiting: &mut Visiting,
+    ) -> fmt::Result {
+        let ptr = self.container_ptr();
+        if let Some(ptr) = ptr {
+            if !visiting.insert(ptr) {
+                return write!(f, "#<cycle>");
+            }
+        }
+        stack::maybe_grow(|| self.write_value(f, visiting))?;
+        if let Some(ptr) = ptr {
+            visiting.remove(&ptr);
+        }
+        Ok(())
+    }
+
+    //  只有唯一的引用时，把其中的容器换成 () 放进 pending
+    fn take_children(&mut self, pending: &mut Vec<Object>) {
+        let mut take = |field: &mut Object| {
+            if field.container_ptr().is_some() {
+                pending.push(mem::replace(field, Object::Nil));
+            }
+        };
+        match self {
+            Object::Pair(pair) => {
+                if let Some(pair) = Rc::get_mut(pair) {
+                    take(pair.car.get_mut());
+                    take(pair.cdr.get_mut());
+                }
+            }
+            Object::Vector(items) => {
+                if let Some(items) = Rc::get_mut(items) {
+                    items.get_mut().iter_mut().for_each(take);
+                }
+            }
+            Object::Box(value) => {
+                if let Some(value) = Rc::get_mut(value) {
+                    take(value.get_mut());
+                }
+            }
+            //  key 不会是容器
+            Object::HashMap(map) => {
+                if let Some(map) = Rc::get_mut(map) {
+                    map.get_mut().values_mut().for_each(take);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    //  可能直接或间接包含自身的值
+    fn container_ptr(&self) -> Option<*const ()> {
+        match self {
+            Object::Pair(pair) => Some(Rc::as_ptr(pair).cast()),
+            Object::Vector(items) => {
+                Some(Rc::as_ptr(items).cast())
+            }
+            Object::HashMap(map) => Some(Rc::as_ptr(map).cast()),
+            Object::Box(value) => Some(Rc::as_ptr(value).cast()),
+            _ => None,
+        }
+    }
+
+    fn write_value(
+        &self,
+        f: &mut fmt::Formatter,
+        visiting: &mut Visiting,
+    ) -> fmt::Result {
         match self {
             Object::Void => write!(f, "Void"),
-            Object::Keyword(s) => write!(f, "{}", s),
-            Object::BinaryOp(s) => write!(f, "{}", s),
             Object::Integer(n) => write!(f, "{}", n),
+            Object::BigInt(n) => write!(f, "{}", n),
+            Object::Rational(n) => write!(f, "{}", n),
             Object::Float(n) => write!(f, "{}", n),
             Object::Bool(b) => write!(f, "{}", b),
+            Object::Char(c) => {
+                match CHAR_NAMES.iter().find(|(_, n)| n == c) {
+                    Some((name, _)) => write!(f, "#\\{}", name),
+                    None if c.is_control() => {
+                        write!(f, "#\\x{:x}", *c as u32)
+                    }
+                    None => write!(f, "#\\{}", c),
+                }
+            }
             Object::Symbol(s) => write!(f, "{}", s),
+            Object::Keyword(s) => write!(f, ":{}", s),
             Object::String(s) => write!(f, "{}", s),
-            Object::Lambda(params, body, _env) => {
-                write!(f, "Lambda(")?;
-                for param in params {
-                    write!(f, "{} ", param)?;
-                }
-                write!(f, ")")?;
-                for expr in (*body).iter() {
-                    write!(f, " {}", expr)?;
+            Object::Builtin(builtin) => {
+                write!(f, "Builtin({})", builtin.name)
+            }
+            Object::Lambda(procedure) => {
+                write!(f, "Lambda({})", procedure.params)?;
+                for obj in &procedure.body {
+                    write!(f, " {}", obj)?;
                 }
                 Ok(())
             }
-            Object::List(list) => {
-                write!(f, "(")?;
-                for (i, obj) in (*list).iter().enumerate() {
+            Object::HashMap(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in
+                    map.borrow().iter().enumerate()
+                {
                     if i > 0 {
                         write!(f, " ")?;
                     }
-                    write!(f, "{}", obj)?;
+                    write!(f, "{} ", key)?;
+                    value.write(f, visiting)?;
                 }
-                write!(f, ")")
+                write!(f, "}}")
             }
-            Object::ListData(list) => {
-                write!(f, "(")?;
-                for (i, obj) in list.iter().enumerate() {
+            Object::Vector(items) => {
+                write!(f, "#(")?;
+                for (i, obj) in items.borrow().iter().enumerate()
+                {
                     if i > 0 {
                         write!(f, " ")?;
                     }
-                    write!(f, "{}", obj)?;
+                    obj.write(f, visiting)?;
                 }
                 write!(f, ")")
             }
+            Object::Box(value) => {
+                write!(f, "#&")?;
+                value.borrow().write(f, visiting)
+            }
+            Object::Nil => write!(f, "()"),
+            Object::Pair(pair) => {
+                write!(f, "(")?;
+                pair.car().write(f, visiting)?;
+                //  cdr 方向回到已经显示过的 pair 时，环显示在尾部
+                let mut entered = vec![];
+                let mut rest = pair.cdr();
+                while let Object::Pair(next) = &rest {
+                    let ptr = Rc::as_ptr(next).cast();
+                    if !visiting.insert(ptr) {
+                        break;
+                    }
+                    entered.push(ptr);
+                    write!(f, " ")?;
+                    next.car().write(f, visiting)?;
+                    let cdr = next.cdr();
+                    rest = cdr;
+                }
+                if !matches!(rest, Object::Nil) {
+                    write!(f, " . ")?;
+                    rest.write(f, visiting)?;
+                }
+                for ptr in entered {
+                    visiting.remove(&ptr);
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Object {
+    pub fn cons(car: Object, cdr: Object) -> Object {
+        Object::Pair(Rc::new(Pair {
+            car: RefCell::new(car),
+            cdr: RefCell::new(cdr),
+            span: None,
+            car_span: None,
+        }))
+    }
+
+    pub fn list(items: Vec<Object>) -> Object {
+        Object::list_with_tail(items, Object::Nil)
+    }
+
+    pub fn vector(items: Vec<Object>) -> Object {
+        Object::Vector(Rc::new(RefCell::new(items)))
+    }
+
+    //  (a b . tail)
+    pub fn list_with_tail(
+        items: Vec<Object>,
+        tail: Object,
+    ) -> Object {
+        items
+            .into_iter()
+            .rev()
+            .fold(tail, |cdr, car| Object::cons(car, cdr))
+    }
+
+    //  parser 读入的 (a b . tail)，每一项都记录源码位置
+    pub fn spanned_list(
+        items: Vec<(Object, Span)>,
+        tail: Object,
+    ) -> Object {
+        items.into_iter().rev().fold(tail, |cdr, (car, span)| {
+            Object::Pair(Rc::new(Pair {
+                car: RefCell::new(car),
+                cdr: RefCell::new(cdr),
+                span: None,
+                car_span: Some(span),
+            }))
+        })
+    }
+
+    //  list 第 index 项在源码中的位置，运行时构造的 list 没有位置
+    pub fn item_span(&self, index: usize) -> Option<Span> {
+        let mut current = self.clone();
+        for _ in 0..index {
+            current = match &current {
+                Object::Pair(pair) => pair.cdr(),
+                _ => return None,
+            };
+        }
+        match &current {
+            Object::Pair(pair) => pair.car_span.clone(),
+            _ => None,
+        }
+    }
+
+    //  在新建的 list 上记录源码位置
+    pub fn with_span(mut self, span: Span) -> Object {
+        if let Object::Pair(pair) = &mut self {
+            if let Some(pair) = Rc::get_mut(pair) {
+                pair.span = Some(span);
+            }
+        }
+        self
+    }
+
+    //  条件判断统一的规则：只有 false 是假值，()、0 等其余值都为真
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Bool(false))
+    }
+
+    //  可以作为哈希表的 key，可变的或者无法比较的值不行
+    pub fn is_hashable(&self) -> bool {
+        !matches!(
+            self,
+            Object::Lambda(..)
+                | Object::HashMap(_)
+                | Object::Vector(_)
+                | Object::Pair(_)
+                | Object::Box(_)
+        )
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            Object::Pair(pair) => pair.span.as_ref(),
+            _ => None,
+        }
+    }
+
+    //  依次访问 list 的元素，遇到非 pair 的尾部或者环时停止
+    pub fn iter(&self) -> ListIter {
+        ListIter {
+            current: self.clone(),
+            slow: self.clone(),
+            move_slow: false,
+            cyclic: false,
+        }
+    }
+
+    //  以 () 结尾的 list，带环的 list 不是
+    pub fn is_list(&self) -> bool {
+        let mut items = self.iter();
+        items.by_ref().for_each(drop);
+        *items.tail() == Object::Nil
+    }
+
+    //  proper list 转为 Vec，带环或者不以 () 结尾时返回 None
+    pub fn to_vec(&self) -> Option<Vec<Object>> {
+        let mut items = self.iter();
+        let vec = items.by_ref().collect();
+        match items.tail() {
+            Object::Nil => Some(vec),
+            _ => None,
+        }
+    }
+}
+
+//  pair 可以被修改，迭代时返回元素的副本
+//  set-cdr! 可以造出环，slow 每两步前进一次，被 current 追上说明有环
+pub struct ListIter {
+    current: Object,
+    slow: Object,
+    move_slow: bool,
+    cyclic: bool,
+}
+
+impl ListIter {
+    //  迭代结束后剩余的部分，proper list 为 ()，有环时为环上的 pair
+    pub fn tail(&self) -> &Object {
+        &self.current
+    }
+}
+
+impl Iterator for ListIter {
+    type Item = Object;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (car, cdr) = match &self.current {
+            Object::Pair(_) if self.cyclic => return None,
+            Object::Pair(pair) => (pair.car(), pair.cdr()),
+            _ => return None,
+        };
+        self.current = cdr;
+        if self.move_slow {
+            let slow = match &self.slow {
+                Object::Pair(pair) => pair.cdr(),
+                _ => Object::Nil,
+            };
+            self.slow = slow;
+        }
+        self.move_slow = !self.move_slow;
+        if let (Object::Pair(fast), Object::Pair(slow)) =
+            (&self.current, &self.slow)
+        {
+            self.cyclic = Rc::ptr_eq(fast, slow);
         }
+        Some(car)
     }
 }
@@ -1,88 +1,604 @@
+use indexmap::IndexMap;
+
 use crate::lexer::*;
+use crate::number::Number;
 use crate::object::*;
+use crate::span::Span;
+use crate::stack;
+use crate::symbol::Symbol;
+use std::cell::RefCell;
 use std::error::Error;
 use std::fmt;
+use std::rc::Rc;
+
+pub use crate::lexer::TokenErrorKind;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ParseError {
+    kind: ParseErrorKind,
     err: String,
+    span: Option<Span>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorKind {
+    //  词法错误，如非法字符、未闭合的字符串
+    Token(TokenErrorKind),
+    //  list 缺少 )，span 指向未闭合的 (
+    MissingRParen,
+    //  多余的 )
+    UnexpectedRParen,
+    //  哈希表字面量缺少 }，span 指向未闭合的 {
+    MissingRBrace,
+    //  多余的 }
+    UnexpectedRBrace,
+    //  哈希表字面量的元素个数为奇数，或者 key 不能作为哈希表的 key
+    InvalidHashLiteral,
+    //  一个完整的 form 之后还有多余的输入
+    TrailingInput,
+    //  需要 ( 却读到了别的 token
+    ExpectedList,
+    //  输入在一个 datum 中途结束，如 ' 之后没有内容
+    UnexpectedEof,
+    //  . 不在 (a . b) 的位置上，如 (. a)、(a . b c)
+    MisplacedDot,
+    //  list 或前缀的嵌套层数超过 MAX_NESTING
+    NestingTooDeep,
+}
+
+impl ParseError {
+    fn new(
+        kind: ParseErrorKind,
+        err: String,
+        span: Option<Span>,
+    ) -> Self {
+        ParseError { kind, err, span }
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    //  输入不完整，补充更多内容后可能读入成功
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self.kind,
+            ParseErrorKind::MissingRParen
+                | ParseErrorKind::MissingRBrace
+                | ParseErrorKind::UnexpectedEof
+                | ParseErrorKind::Token(
+                    TokenErrorKind::UnterminatedString
+                        | TokenErrorKind::UnterminatedComment
+                )
+        )
+    }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Parse error: {}", self.err)
+        write!(f, "Parse error: {}", self.err)?;
+        if let Some(span) = &self.span {
+            write!(f, " at {}\n{}", span, span.snippet())?;
+        }
+        Ok(())
     }
 }
 
 impl Error for ParseError {}
 
+impl From<TokenError> for ParseError {
+    fn from(err: TokenError) -> Self {
+        ParseError::new(
+            ParseErrorKind::Token(err.kind),
+            err.err,
+            Some(err.span),
+        )
+    }
+}
+
 pub fn parse(input: &str) -> Result<Object, ParseError> {
-    let tokens_result = tokenize(input);
-    if tokens_result.is_err() {
-        return Err(ParseError {
-            err: format!("Tokenize error"),
-        });
+    let tokens = tokenize(input)?;
+    check_nesting(&tokens)?;
+
+    let mut tokens =
+        tokens.into_iter().rev().collect::<Vec<_>>();
+    skip_datum_comments(&mut tokens)?;
+    let obj = match tokens.last().map(|t| &t.token) {
+        Some(
+            Token::Quote
+            | Token::Quasiquote
+            | Token::Unquote
+            | Token::UnquoteSplicing,
+        ) => parse_datum(&mut tokens)?.0,
+        _ => parse_list(&mut tokens)?,
+    };
+
+    skip_datum_comments(&mut tokens)?;
+    match tokens.pop() {
+        None => Ok(obj),
+        Some(SpannedToken { token, span }) => {
+            let kind = match token {
+                Token::RParen => {
+                    ParseErrorKind::UnexpectedRParen
+                }
+                _ => ParseErrorKind::TrailingInput,
+            };
+            Err(ParseError::new(
+                kind,
+                format!(
+                    "Unexpected {:?} after end of form",
+                    token
+                ),
+                Some(span),
+            ))
+        }
+    }
+}
+
+//  读入所有顶层 datum，包括 42、x 这样的单个原子
+pub fn parse_program(
+    input: &str,
+) -> Result<Vec<Object>, ParseError> {
+    let program = parse_spanned_program(input)?;
+    Ok(program.into_iter().map(|(obj, _)| obj).collect())
+}
+
+//  同 parse_program，同时返回每个 datum 在源码中的位置
+pub fn parse_spanned_program(
+    input: &str,
+) -> Result<Vec<(Object, Span)>, ParseError> {
+    let tokens = tokenize(input)?;
+    check_nesting(&tokens)?;
+
+    let mut tokens =
+        tokens.into_iter().rev().collect::<Vec<_>>();
+    let mut program = vec![];
+    loop {
+        skip_datum_comments(&mut tokens)?;
+        if tokens.is_empty() {
+            break;
+        }
+        program.push(parse_datum(&mut tokens)?);
+    }
+    Ok(program)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadStatus {
+    //  已读入完整的 form，附带累积的全部源码
+    Complete(String),
+    //  还需要更多输入
+    Incomplete,
+}
+
+//  逐行读入，直到累积的输入构成完整的 form，供 REPL 使用
+#[derive(Debug, Default)]
+pub struct Reader {
+    buffer: String,
+}
+
+impl Reader {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
     }
 
-    let mut tokens = tokens_result
-        .unwrap()
-        .into_iter()
-        .rev()
-        .collect::<Vec<_>>();
-    let parse_list = parse_list(&mut tokens)?;
-    Ok(parse_list)
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    pub fn feed(
+        &mut self,
+        line: &str,
+    ) -> Result<ReadStatus, ParseError> {
+        if !self.buffer.is_empty() {
+            self.buffer.push('\n');
+        }
+        self.buffer.push_str(line);
+
+        match parse_program(&self.buffer) {
+            Ok(_) => {
+                let source = std::mem::take(&mut self.buffer);
+                Ok(ReadStatus::Complete(source))
+            }
+            Err(err) if err.is_incomplete() => {
+                Ok(ReadStatus::Incomplete)
+            }
+            Err(err) => {
+                self.reset();
+                Err(err)
+            }
+        }
+    }
 }
+
 pub fn parse_list(
-    tokens: &mut Vec<Token>,
+    tokens: &mut Vec<SpannedToken>,
 ) -> Result<Object, ParseError> {
-    let token = tokens.pop();
-    if token != Some(Token::LParen) {
-        return Err(ParseError {
-            err: format!("Expected LParen, found {:?}", token),
-        });
-    }
+    let open = match tokens.pop() {
+        Some(SpannedToken {
+            token: Token::LParen,
+            span,
+        }) => span,
+        Some(SpannedToken { token, span }) => {
+            return Err(ParseError::new(
+                ParseErrorKind::ExpectedList,
+                format!("Expected LParen, found {:?}", token),
+                Some(span),
+            ))
+        }
+        None => {
+            return Err(ParseError::new(
+                ParseErrorKind::UnexpectedEof,
+                "Expected LParen, found end of input"
+                    .to_string(),
+                None,
+            ))
+        }
+    };
 
     let mut list = vec![];
-    while !tokens.is_empty() {
-        let token = tokens.pop();
-        if token.is_none() {
-            return Err(ParseError {
-                err: format!("Did not find enough tokens"),
+    loop {
+        skip_datum_comments(tokens)?;
+        let token = match tokens.pop() {
+            Some(token) => token,
+            None => break,
+        };
+        if token.token == Token::RParen {
+            return Ok(Object::spanned_list(list, Object::Nil)
+                .with_span(open.to(&token.span)));
+        }
+        if token.token == Token::Dot {
+            return parse_dotted_tail(
+                list, token.span, open, tokens,
+            );
+        }
+        tokens.push(token);
+        list.push(parse_datum(tokens)?);
+    }
+    Err(ParseError::new(
+        ParseErrorKind::MissingRParen,
+        "Missing RParen to close this list".to_string(),
+        Some(open),
+    ))
+}
+
+//  (a b . c) 中 . 之后的部分，结果是一串 pair
+fn parse_dotted_tail(
+    list: Vec<(Object, Span)>,
+    dot: Span,
+    open: Span,
+    tokens: &mut Vec<SpannedToken>,
+) -> Result<Object, ParseError> {
+    let misplaced = |span| {
+        ParseError::new(
+            ParseErrorKind::MisplacedDot,
+            "Unexpected . in list".to_string(),
+            Some(span),
+        )
+    };
+    if list.is_empty() {
+        return Err(misplaced(dot));
+    }
+
+    let missing_rparen = || {
+        ParseError::new(
+            ParseErrorKind::MissingRParen,
+            "Missing RParen to close this list".to_string(),
+            Some(open.clone()),
+        )
+    };
+    skip_datum_comments(tokens)?;
+    match tokens.last().map(|t| &t.token) {
+        None => return Err(missing_rparen()),
+        Some(Token::RParen) => return Err(misplaced(dot)),
+        _ => {}
+    }
+    let (tail, _) = parse_datum(tokens)?;
+
+    skip_datum_comments(tokens)?;
+    match tokens.pop() {
+        Some(SpannedToken {
+            token: Token::RParen,
+            span,
+        }) => Ok(Object::spanned_list(list, tail)
+            .with_span(open.to(&span))),
+        Some(_) => Err(misplaced(dot)),
+        None => Err(missing_rparen()),
+    }
+}
+
+//  丢弃 #; 之后的 datum
+fn skip_datum_comments(
+    tokens: &mut Vec<SpannedToken>,
+) -> Result<(), ParseError> {
+    while tokens.last().map(|t| &t.token)
+        == Some(&Token::DatumComment)
+    {
+        let comment = tokens.pop().unwrap();
+        if tokens.is_empty() {
+            return Err(ParseError::new(
+                ParseErrorKind::UnexpectedEof,
+                "Expected datum after #;".to_string(),
+                Some(comment.span),
+            ));
+        }
+        parse_datum(tokens)?;
+    }
+    Ok(())
+}
+
+//  读入、求值和释放嵌套的 list 都是递归的，层数过多会耗尽栈空间
+const MAX_NESTING: usize = 1000;
+
+//  ' 等前缀同样会递归读入后面的 datum，与括号一起计入嵌套层数
+fn check_nesting(
+    tokens: &[SpannedToken],
+) -> Result<(), ParseError> {
+    let mut levels = vec![];
+    let mut depth = 0;
+    let mut prefixes = 0;
+    for SpannedToken { token, span } in tokens {
+        match token {
+            Token::Quote
+            | Token::Quasiquote
+            | Token::Unquote
+            | Token::UnquoteSplicing
+            | Token::DatumComment => prefixes += 1,
+            Token::LParen
+            | Token::VectorOpen
+            | Token::LBrace => {
+                levels.push(prefixes + 1);
+                depth += prefixes + 1;
+                prefixes = 0;
+            }
+            Token::RParen | Token::RBrace => {
+                depth -= levels.pop().unwrap_or(0);
+                prefixes = 0;
+            }
+            _ => prefixes = 0,
+        }
+        if depth + prefixes > MAX_NESTING {
+            return Err(ParseError::new(
+                ParseErrorKind::NestingTooDeep,
+                format!(
+                    "Nesting deeper than {} levels",
+                    MAX_NESTING
+                ),
+                Some(span.clone()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+//  读入一个完整的 datum，同时返回它在源码中的位置
+fn parse_datum(
+    tokens: &mut Vec<SpannedToken>,
+) -> Result<(Object, Span), ParseError> {
+    stack::maybe_grow(|| read_datum(tokens))
+}
+
+fn read_datum(
+    tokens: &mut Vec<SpannedToken>,
+) -> Result<(Object, Span), ParseError> {
+    skip_datum_comments(tokens)?;
+    let SpannedToken { token, span } = match tokens.pop() {
+        Some(token) => token,
+        None => {
+            return Err(ParseError::new(
+                ParseErrorKind::UnexpectedEof,
+                "Unexpected end of input".to_string(),
+                None,
+            ))
+        }
+    };
+
+    let obj = match token {
+        Token::Integer(i) => Object::Integer(i),
+        Token::BigInt(n) => Object::BigInt(n),
+        Token::Rational(n) => Number::Rational(n).into_object(),
+        Token::Float(f) => Object::Float(f),
+        Token::Bool(b) => Object::Bool(b),
+        Token::Char(c) => Object::Char(c),
+        Token::Nil => Object::Nil,
+        Token::String(s) => Object::String(s),
+        Token::Symbol(s) => Object::Symbol(s),
+        Token::Keyword(s) => Object::Keyword(s),
+        Token::LParen => {
+            tokens.push(SpannedToken {
+                token: Token::LParen,
+                span: span.clone(),
             });
+            let list = parse_list(tokens)?;
+            let span = list.span().cloned().unwrap_or(span);
+            return Ok((list, span));
+        }
+        Token::RParen => {
+            return Err(ParseError::new(
+                ParseErrorKind::UnexpectedRParen,
+                "Unexpected RParen".to_string(),
+                Some(span),
+            ))
         }
+        Token::LBrace => return parse_hash_map(span, tokens),
+        Token::VectorOpen => return parse_vector(span, tokens),
+        Token::RBrace => {
+            return Err(ParseError::new(
+                ParseErrorKind::UnexpectedRBrace,
+                "Unexpected RBrace".to_string(),
+                Some(span),
+            ))
+        }
+        //  'x => (quote x), `x => (quasiquote x) ...
+        Token::Quote => {
+            return parse_quoted("quote", span, tokens)
+        }
+        Token::Quasiquote => {
+            return parse_quoted("quasiquote", span, tokens)
+        }
+        Token::Unquote => {
+            return parse_quoted("unquote", span, tokens)
+        }
+        Token::UnquoteSplicing => {
+            return parse_quoted(
+                "unquote-splicing",
+                span,
+                tokens,
+            )
+        }
+        Token::Dot => {
+            return Err(ParseError::new(
+                ParseErrorKind::MisplacedDot,
+                "Unexpected . outside of a list".to_string(),
+                Some(span),
+            ))
+        }
+        Token::DatumComment => unreachable!(),
+    };
+    Ok((obj, span))
+}
 
-        let t = token.unwrap();
-        match t {
-            Token::Keyword(k) => list.push(Object::Keyword(k)),
-            Token::BinaryOp(s) => list.push(Object::BinaryOp(s)),
-            Token::Integer(i) => list.push(Object::Integer(i)),
-            Token::Float(f) => list.push(Object::Float(f)),
-            Token::String(s) => list.push(Object::String(s)),
-            Token::Symbol(s) => list.push(Object::Symbol(s)),
-            Token::LParen => {
-                tokens.push(Token::LParen);
-                let sub_list = parse_list(tokens)?;
-                list.push(sub_list);
+//  {k1 v1 k2 v2 ...}，key 是字面量，value 保存为表达式，求值字面量时才求值
+fn parse_hash_map(
+    open: Span,
+    tokens: &mut Vec<SpannedToken>,
+) -> Result<(Object, Span), ParseError> {
+    let mut items = vec![];
+    let close = loop {
+        skip_datum_comments(tokens)?;
+        match tokens.pop() {
+            Some(SpannedToken {
+                token: Token::RBrace,
+                span,
+            }) => break span,
+            Some(token) => {
+                tokens.push(token);
+                items.push(parse_datum(tokens)?);
             }
-            Token::RParen => {
-                return Ok(Object::List(list));
+            None => {
+                return Err(ParseError::new(
+                    ParseErrorKind::MissingRBrace,
+                    "Missing RBrace to close this hash map"
+                        .to_string(),
+                    Some(open),
+                ))
             }
         }
+    };
+    let span = open.to(&close);
+
+    if items.len() % 2 != 0 {
+        return Err(ParseError::new(
+            ParseErrorKind::InvalidHashLiteral,
+            "Hash map literal needs a value for every key"
+                .to_string(),
+            Some(span),
+        ));
+    }
+
+    let mut map = IndexMap::new();
+    let mut items = items.into_iter();
+    while let (Some((key, key_span)), Some((value, _))) =
+        (items.next(), items.next())
+    {
+        if !key.is_hashable() {
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidHashLiteral,
+                format!("Unhashable key {}", key),
+                Some(key_span),
+            ));
+        }
+        map.insert(key, value);
     }
-    Ok(Object::List(list))
+    Ok((Object::HashMap(Rc::new(RefCell::new(map))), span))
+}
+
+//  #(a b c) 中 #( 之后的部分，元素不求值
+fn parse_vector(
+    open: Span,
+    tokens: &mut Vec<SpannedToken>,
+) -> Result<(Object, Span), ParseError> {
+    let mut items = vec![];
+    let close = loop {
+        skip_datum_comments(tokens)?;
+        match tokens.pop() {
+            Some(SpannedToken {
+                token: Token::RParen,
+                span,
+            }) => break span,
+            Some(token) => {
+                tokens.push(token);
+                items.push(parse_datum(tokens)?.0);
+            }
+            None => {
+                return Err(ParseError::new(
+                    ParseErrorKind::MissingRParen,
+                    "Missing RParen to close this vector"
+                        .to_string(),
+                    Some(open),
+                ))
+            }
+        }
+    };
+    Ok((Object::vector(items), open.to(&close)))
+}
+
+fn parse_quoted(
+    keyword: &str,
+    prefix: Span,
+    tokens: &mut Vec<SpannedToken>,
+) -> Result<(Object, Span), ParseError> {
+    let (datum, datum_span) = match parse_datum(tokens) {
+        Ok(datum) => datum,
+        Err(err)
+            if err.kind == ParseErrorKind::UnexpectedEof =>
+        {
+            return Err(ParseError::new(
+                ParseErrorKind::UnexpectedEof,
+                format!(
+                    "Expected datum after {}",
+                    prefix.text()
+                ),
+                Some(prefix),
+            ))
+        }
+        Err(err) => return Err(err),
+    };
+    let span = prefix.to(&datum_span);
+    let list = Object::spanned_list(
+        vec![
+            (Object::Symbol(Symbol::intern(keyword)), prefix),
+            (datum, datum_span),
+        ],
+        Object::Nil,
+    )
+    .with_span(span.clone());
+    Ok((list, span))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn list_of(items: Vec<Object>) -> Object {
+        Object::list(items)
+    }
+
     #[test]
     fn test_add() {
         let list = parse("(+ 1 2)").unwrap();
         assert_eq!(
             list,
-            Object::List(vec![
-                Object::BinaryOp("+".to_string()),
+            list_of(vec![
+                Object::Symbol(Symbol::intern("+")),
                 Object::Integer(1),
                 Object::Integer(2)
             ])
@@ -99,27 +615,442 @@ mod tests {
         let list = parse(program).unwrap();
         assert_eq!(
             list,
-            Object::List(vec![
-                Object::List(vec![
-                    Object::Keyword("define".to_string()),
-                    Object::Symbol("r".to_string()),
+            list_of(vec![
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("define")),
+                    Object::Symbol(Symbol::intern("r")),
                     Object::Integer(10),
                 ]),
-                Object::List(vec![
-                    Object::Keyword("define".to_string()),
-                    Object::Symbol("pi".to_string()),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("define")),
+                    Object::Symbol(Symbol::intern("pi")),
                     Object::Integer(314),
                 ]),
-                Object::List(vec![
-                    Object::BinaryOp("*".to_string()),
-                    Object::Symbol("pi".to_string()),
-                    Object::List(vec![
-                        Object::BinaryOp("*".to_string()),
-                        Object::Symbol("r".to_string()),
-                        Object::Symbol("r".to_string()),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("*")),
+                    Object::Symbol(Symbol::intern("pi")),
+                    list_of(vec![
+                        Object::Symbol(Symbol::intern("*")),
+                        Object::Symbol(Symbol::intern("r")),
+                        Object::Symbol(Symbol::intern("r")),
                     ]),
                 ]),
             ])
         );
     }
+
+    #[test]
+    fn test_list_span() {
+        let program = "(begin\n  (define r 10)\n  (* r r))";
+        let obj = parse(program).unwrap();
+        let span = obj.span().expect("expected a spanned list");
+        assert_eq!((span.start, span.end), (0, program.len()));
+
+        let inner = obj.iter().nth(2).unwrap();
+        let span =
+            inner.span().expect("expected a spanned list");
+        assert_eq!((span.line, span.column), (3, 3));
+        assert_eq!(span.text(), "(* r r)");
+
+        //  atom 的位置记录在包含它的 pair 上
+        let span = inner.item_span(2).unwrap();
+        assert_eq!((span.line, span.column), (3, 8));
+        assert_eq!(span.text(), "r");
+        assert_eq!(
+            obj.item_span(1).unwrap().text(),
+            "(define r 10)"
+        );
+        let quoted = parse("'(a\n b)").unwrap();
+        assert_eq!(quoted.item_span(0).unwrap().text(), "'");
+        assert_eq!(
+            quoted.item_span(1).unwrap().text(),
+            "(a\n b)"
+        );
+        assert!(Object::list(vec![Object::Nil])
+            .item_span(0)
+            .is_none());
+    }
+
+    #[test]
+    fn test_error_snippet() {
+        let err = parse("\n  foo").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse error: Expected LParen, found Symbol(\"foo\") \
+             at 2:3\n  |\n2 |   foo\n  |   ^^^"
+        );
+    }
+
+    #[test]
+    fn test_quote_syntax() {
+        let list = parse("(f 'x `(a ,b ,@c))").unwrap();
+        assert_eq!(
+            list,
+            list_of(vec![
+                Object::Symbol(Symbol::intern("f")),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("quote")),
+                    Object::Symbol(Symbol::intern("x")),
+                ]),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("quasiquote")),
+                    list_of(vec![
+                        Object::Symbol(Symbol::intern("a")),
+                        list_of(vec![
+                            Object::Symbol(Symbol::intern(
+                                "unquote"
+                            )),
+                            Object::Symbol(Symbol::intern("b")),
+                        ]),
+                        list_of(vec![
+                            Object::Symbol(Symbol::intern(
+                                "unquote-splicing"
+                            )),
+                            Object::Symbol(Symbol::intern("c")),
+                        ]),
+                    ]),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_quote_top_level() {
+        let list = parse("'(1 2)").unwrap();
+        assert_eq!(
+            list,
+            list_of(vec![
+                Object::Symbol(Symbol::intern("quote")),
+                list_of(vec![
+                    Object::Integer(1),
+                    Object::Integer(2)
+                ]),
+            ])
+        );
+        assert_eq!(list.span().unwrap().text(), "'(1 2)");
+    }
+
+    #[test]
+    fn test_comments_are_whitespace() {
+        let program = "; leading comment
+            #| block
+               #| nested |# |#
+            (begin ; after head
+                (define #| inline |# r 10) ;; trailing
+                #;(define r 20)
+                #; #;1 2
+                (* r r #;r)
+                #;
+                unused)
+            ; end of file";
+        let list = parse(program).unwrap();
+        assert_eq!(
+            list,
+            list_of(vec![
+                Object::Symbol(Symbol::intern("begin")),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("define")),
+                    Object::Symbol(Symbol::intern("r")),
+                    Object::Integer(10),
+                ]),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("*")),
+                    Object::Symbol(Symbol::intern("r")),
+                    Object::Symbol(Symbol::intern("r")),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_datum_comment_inside_quote() {
+        let list = parse("(f '#;a b)").unwrap();
+        assert_eq!(
+            list,
+            list_of(vec![
+                Object::Symbol(Symbol::intern("f")),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("quote")),
+                    Object::Symbol(Symbol::intern("b")),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_datum_comment_at_top_level() {
+        let list = parse("#;(ignored) (+ 1 2)").unwrap();
+        assert_eq!(
+            list,
+            list_of(vec![
+                Object::Symbol(Symbol::intern("+")),
+                Object::Integer(1),
+                Object::Integer(2),
+            ])
+        );
+        assert!(parse("(a #;)").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_error() {
+        let err = parse("(print \"oops)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parse error: Unterminated string at 1:8\n  |\n\
+             1 | (print \"oops)\n  |        ^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        let list = parse(r"(f #t false #\a nil ())").unwrap();
+        assert_eq!(
+            list,
+            list_of(vec![
+                Object::Symbol(Symbol::intern("f")),
+                Object::Bool(true),
+                Object::Bool(false),
+                Object::Char('a'),
+                Object::list(vec![]),
+                list_of(vec![]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let program = "
+            (define r 10) ; radius
+            42 x \"s\" 'y
+            #;(ignored)
+            (* r r)";
+        let forms = parse_program(program).unwrap();
+        assert_eq!(
+            forms,
+            vec![
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("define")),
+                    Object::Symbol(Symbol::intern("r")),
+                    Object::Integer(10),
+                ]),
+                Object::Integer(42),
+                Object::Symbol(Symbol::intern("x")),
+                Object::String("s".to_string()),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("quote")),
+                    Object::Symbol(Symbol::intern("y")),
+                ]),
+                list_of(vec![
+                    Object::Symbol(Symbol::intern("*")),
+                    Object::Symbol(Symbol::intern("r")),
+                    Object::Symbol(Symbol::intern("r")),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_program() {
+        assert_eq!(
+            parse_program("  ; nothing here\n").unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_missing_rparen() {
+        let err = parse("(begin\n  (define x (+ 1 2)\n  x)")
+            .unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MissingRParen);
+        assert_eq!(err.span().unwrap().text(), "(");
+        assert_eq!(err.span().unwrap().line, 1);
+
+        let err = parse_program("(a) '(b c").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MissingRParen);
+        assert_eq!(err.span().unwrap().column, 6);
+    }
+
+    #[test]
+    fn test_extra_rparen() {
+        let err = parse("(+ 1 2))").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedRParen);
+        assert_eq!(err.span().unwrap().column, 8);
+
+        let err = parse_program("(a))").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedRParen);
+    }
+
+    #[test]
+    fn test_trailing_input() {
+        let err = parse("(+ 1 2) (+ 3 4)").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::TrailingInput);
+        assert_eq!(err.span().unwrap().column, 9);
+
+        assert!(parse("(+ 1 2) ; comment #;(ignored)").is_ok());
+    }
+
+    #[test]
+    fn test_dotted_list() {
+        let list = parse("(a b . c)").unwrap();
+        assert_eq!(
+            list,
+            Object::list_with_tail(
+                vec![
+                    Object::Symbol(Symbol::intern("a")),
+                    Object::Symbol(Symbol::intern("b")),
+                ],
+                Object::Symbol(Symbol::intern("c")),
+            )
+        );
+
+        for input in ["(. a)", "(a . b c)", "(a .)", "(a . . b)"]
+        {
+            let err = parse(input).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::MisplacedDot);
+        }
+
+        let err = parse("(a . ").unwrap_err();
+        assert!(err.is_incomplete());
+    }
+
+    #[test]
+    fn test_hash_map_literal() {
+        let obj = parse_program("{a 1 \"b\" (2 3)}").unwrap();
+        let map = match &obj[0] {
+            Object::HashMap(map) => map.borrow().clone(),
+            _ => panic!("expected a hash map"),
+        };
+        assert_eq!(map.len(), 2);
+        assert_eq!(
+            map[&Object::Symbol(Symbol::intern("a"))],
+            Object::Integer(1)
+        );
+        assert_eq!(
+            map[&Object::String("b".to_string())],
+            list_of(vec![
+                Object::Integer(2),
+                Object::Integer(3)
+            ])
+        );
+
+        let err = parse_program("{a 1 b}").unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ParseErrorKind::InvalidHashLiteral
+        );
+        let err = parse_program("{{} 1}").unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ParseErrorKind::InvalidHashLiteral
+        );
+        let err = parse_program("(a })").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedRBrace);
+        let err = parse_program("{a 1").unwrap_err();
+        assert!(err.is_incomplete());
+    }
+
+    #[test]
+    fn test_vector_literal() {
+        let obj = parse_program("#(1 (a) #(b))").unwrap();
+        assert_eq!(
+            obj[0],
+            Object::vector(vec![
+                Object::Integer(1),
+                list_of(vec![Object::Symbol(Symbol::intern(
+                    "a"
+                ))]),
+                Object::vector(vec![Object::Symbol(
+                    Symbol::intern("b")
+                )]),
+            ])
+        );
+        assert_eq!(obj[0].to_string(), "#(1 (a) #(b))");
+
+        let err = parse_program("#(1 . 2)").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MisplacedDot);
+        let err = parse_program("#(1 2").unwrap_err();
+        assert!(err.is_incomplete());
+    }
+
+    #[test]
+    fn test_unexpected_character() {
+        let err = parse("(a @b)").unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ParseErrorKind::Token(
+                TokenErrorKind::UnexpectedChar
+            )
+        );
+        assert_eq!(err.span().unwrap().column, 4);
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        let err = parse("(a ')").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedRParen);
+
+        let err = parse_program("'").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedEof);
+
+        let err = parse("").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedEof);
+
+        let err = parse("x").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::ExpectedList);
+    }
+
+    #[test]
+    fn test_reader_multi_line() {
+        let mut reader = Reader::new();
+        assert_eq!(
+            reader.feed("(define (sqr x)").unwrap(),
+            ReadStatus::Incomplete
+        );
+        assert_eq!(
+            reader.feed("  (* x x))").unwrap(),
+            ReadStatus::Complete(
+                "(define (sqr x)\n  (* x x))".to_string()
+            )
+        );
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_reader_incomplete_tokens() {
+        let mut reader = Reader::new();
+        for line in ["(print \"multi", "line\")"] {
+            reader.feed(line).unwrap();
+        }
+        assert!(reader.is_empty());
+
+        for (line, status) in [
+            ("#| comment", ReadStatus::Incomplete),
+            ("|# 'x '", ReadStatus::Incomplete),
+            (
+                "y",
+                ReadStatus::Complete(
+                    "#| comment\n|# 'x '\ny".to_string(),
+                ),
+            ),
+        ] {
+            assert_eq!(reader.feed(line).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn test_reader_error_resets() {
+        let mut reader = Reader::new();
+        assert_eq!(
+            reader.feed("(a").unwrap(),
+            ReadStatus::Incomplete
+        );
+        let err = reader.feed("b))").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnexpectedRParen);
+        assert!(reader.is_empty());
+
+        assert_eq!(
+            reader.feed("42").unwrap(),
+            ReadStatus::Complete("42".to_string())
+        );
+    }
 }
@@ -0,0 +1,109 @@
+use std::{fmt, rc::Rc};
+
+//  源码位置：字节偏移 + 行列号，行列从 1 开始
+#[derive(Clone, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    source: Rc<str>,
+}
+
+impl Span {
+    pub fn new(
+        source: Rc<str>,
+        start: usize,
+        end: usize,
+        line: usize,
+        column: usize,
+    ) -> Self {
+        Span {
+            start,
+            end,
+            line,
+            column,
+            source,
+        }
+    }
+
+    //  合并两个 span，得到覆盖两者的区间
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end.max(self.start),
+            line: self.line,
+            column: self.column,
+            source: self.source.clone(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.source[self.start..self.end]
+    }
+
+    //  渲染出错的那一行，并在下方用 ^ 标出 span 覆盖的部分
+    pub fn snippet(&self) -> String {
+        let line_start = self.source[..self.start]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let line_end = self.source[self.start..]
+            .find('\n')
+            .map_or(self.source.len(), |i| self.start + i);
+        let line_text = &self.source[line_start..line_end];
+
+        let gutter = self.line.to_string().len();
+        let offset =
+            self.source[line_start..self.start].chars().count();
+        let width = self.source
+            [self.start..self.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        format!(
+            "{:gutter$} |\n{} | {}\n{:gutter$} | {}{}",
+            "",
+            self.line,
+            line_text,
+            "",
+            " ".repeat(offset),
+            "^".repeat(width),
+            gutter = gutter,
+        )
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Span({}..{} @ {})",
+            self.start, self.end, self
+        )
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snippet() {
+        let source: Rc<str> =
+            Rc::from("(define x 1)\n(foo bar)");
+        let span = Span::new(source, 18, 21, 2, 6);
+        assert_eq!(span.text(), "bar");
+        assert_eq!(span.to_string(), "2:6");
+        assert_eq!(
+            span.snippet(),
+            "  |\n2 | (foo bar)\n  |      ^^^"
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//  递归读入和求值时使用：剩余栈空间不足 RED_ZONE 时，在堆上分配 SEGMENT 大小的新栈段继续执行
+const RED_ZONE: usize = 64 * 1024;
+const SEGMENT: usize = 1024 * 1024;
+
+pub(crate) fn maybe_grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT, f)
+}
@@ -0,0 +1,71 @@
+use std::{
+    cell::RefCell, collections::HashMap, fmt,
+    marker::PhantomData,
+};
+
+//  驻留的 symbol，相同名字的 symbol 共享同一个 id，比较和哈希只需处理 id
+//  id 只在所属线程的 INTERNER 中有效，PhantomData 让 Symbol 不能跨线程传递
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32, PhantomData<*const ()>);
+
+//  名字在整个程序运行期间都不会释放，因此可以直接返回 &'static str
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::default();
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(&sym) = interner.ids.get(name) {
+                return sym;
+            }
+
+            let name: &'static str =
+                Box::leak(name.to_string().into_boxed_str());
+            let sym =
+                Symbol(interner.names.len() as u32, PhantomData);
+            interner.names.push(name);
+            interner.ids.insert(name, sym);
+            sym
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with(|interner| {
+            interner.borrow().names[self.0 as usize]
+        })
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let foo = Symbol::intern("foo");
+        assert_eq!(foo, Symbol::intern("foo"));
+        assert_ne!(foo, Symbol::intern("bar"));
+        assert_eq!(foo.as_str(), "foo");
+        assert_eq!(format!("{} {:?}", foo, foo), "foo \"foo\"");
+    }
+}
@@ -0,0 +1,299 @@
+//  任意输入都不能让求值器 panic 或栈溢出，只能得到值或者 EvalError
+use std::{cell::RefCell, rc::Rc};
+
+use proptest::prelude::*;
+use rlisp::env::Env;
+use rlisp::eval::{eval, eval_program, EvalErrorKind};
+
+//  括号和前缀
+const DELIMITERS: &[&str] = &[
+    "(", "(", "(", ")", ")", ")", "#(", "{", "}", "'", "`", ",",
+    ",@", ".", "#;",
+];
+
+//  特殊形式、内置函数以及各种边界值
+const ATOMS: &[&str] = &[
+    "begin",
+    "define",
+    "set!",
+    "lambda",
+    "if",
+    "cond",
+    "else",
+    "let",
+    "and",
+    "or",
+    "quote",
+    "quasiquote",
+    "unquote",
+    "unquote-splicing",
+    "list",
+    "car",
+    "cdr",
+    "cons",
+    "length",
+    "null?",
+    "pair?",
+    "list?",
+    "set-car!",
+    "set-cdr!",
+    "eval",
+    "error",
+    "string->symbol",
+    "symbol->string",
+    "keyword?",
+    "procedure-arity",
+    "make-hash",
+    "hash-ref",
+    "hash-set!",
+    "hash-remove!",
+    "hash-keys",
+    "hash->list",
+    "hash-for-each",
+    "hash-map",
+    "vector",
+    "make-vector",
+    "vector-ref",
+    "vector-set!",
+    "vector-copy",
+    "vector->list",
+    "list->vector",
+    "vector-map",
+    "vector-fill!",
+    "box",
+    "unbox",
+    "set-box!",
+    "+",
+    "-",
+    "*",
+    "/",
+    "%",
+    "quotient",
+    "remainder",
+    "modulo",
+    "numerator",
+    "denominator",
+    "exact->inexact",
+    "inexact->exact",
+    "=",
+    "<",
+    ">",
+    "x",
+    "f",
+    ":x",
+    "#!optional",
+    "#!key",
+    "0",
+    "1",
+    "-1",
+    "2/3",
+    "0.0",
+    "-0.5",
+    "9223372036854775807",
+    "-9223372036854775808",
+    "99999999999999999999",
+    "+inf.0",
+    "+nan.0",
+    "#t",
+    "#f",
+    "#\\a",
+    "\"s\"",
+    "\"\"",
+    "()",
+];
+
+//  任意排列的 token，括号多半不配对，主要覆盖 lexer 和 parser
+fn token_soup() -> impl Strategy<Value = String> {
+    let token = prop_oneof![
+        prop::sample::select(DELIMITERS),
+        prop::sample::select(ATOMS),
+    ];
+    prop::collection::vec(token, 0..40)
+        .prop_map(|tokens| tokens.join(" "))
+}
+
+//  括号配对的 form，大部分能读入，主要覆盖求值
+fn forms() -> impl Strategy<Value = String> {
+    let atom =
+        prop::sample::select(ATOMS).prop_map(str::to_string);
+    let form = atom.prop_recursive(5, 64, 6, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..6).prop_map(
+                |items| format!("({})", items.join(" "))
+            ),
+            prop::collection::vec(inner.clone(), 0..4).prop_map(
+                |items| format!("#({})", items.join(" "))
+            ),
+            (
+                prop::sample::select(&["'", "`", ",", ",@"][..]),
+                inner
+            )
+                .prop_map(|(prefix, form)| format!(
+                    "{}{}",
+                    prefix, form
+                )),
+        ]
+    });
+    prop::collection::vec(form, 1..4)
+        .prop_map(|forms| forms.join("\n"))
+}
+
+//  结果和错误都转为字符串，确保显示时也不会 panic
+fn run(input: &str) {
+    let env = Rc::new(RefCell::new(Env::new()));
+    if let Err(err) = eval(input, env.clone()) {
+        err.to_string();
+    }
+    match eval_program(input, env) {
+        Ok(value) => value.to_string(),
+        Err(err) => err.to_string(),
+    };
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(1000))]
+
+    #[test]
+    fn arbitrary_text_does_not_panic(input in "\\PC{0,64}") {
+        run(&input);
+    }
+
+    #[test]
+    fn token_soup_does_not_panic(input in token_soup()) {
+        run(&input);
+    }
+
+    #[test]
+    fn forms_do_not_panic(input in forms()) {
+        run(&input);
+    }
+}
+
+#[test]
+fn known_crashers() {
+    let inputs = [
+        "()",
+        "(car (list))",
+        "(|| 1 2)",
+        "(1 2)",
+        "(#t 1)",
+        "((lambda (x) x))",
+        "(make-vector 1000000000000000)",
+        "(vector-ref (make-vector 2) 9223372036854775807)",
+        "(quotient -9223372036854775808 -1)",
+        "(modulo 1 0.0)",
+        "(inexact->exact +inf.0)",
+        "(define (f) (+ 1 1)) (= (list f) (list f))",
+        "(define)",
+        "(define (f))",
+        "(define x (list 1 2)) (set-cdr! x x) (length x)",
+        "(define x (list 1 2)) (set-cdr! x x) x",
+        "(define v (vector 1)) (vector-set! v 0 v) v",
+        "(define v (vector 1)) (vector-set! v 0 v) \
+         (define w (vector 1)) (vector-set! w 0 w) \
+         (= (list v) (list w))",
+    ];
+    for input in inputs {
+        run(input);
+    }
+}
+
+#[test]
+fn deep_nesting() {
+    let nested = |n| "(".repeat(n) + &")".repeat(n);
+    run(&nested(1000));
+
+    let env = Rc::new(RefCell::new(Env::new()));
+    let err = eval(&nested(100_000), env.clone()).unwrap_err();
+    assert!(err.to_string().contains("Nesting deeper than"));
+    let err = eval(&"'".repeat(100_000), env).unwrap_err();
+    assert!(err.to_string().contains("Nesting deeper than"));
+}
+
+#[test]
+fn runaway_recursion() {
+    let env = Rc::new(RefCell::new(Env::new()));
+    let program = "
+      (define (f n) (+ 1 (f n)))
+      (f 0)
+      ";
+    let err = eval_program(program, env.clone()).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &EvalErrorKind::Runtime(
+            "Maximum recursion depth exceeded".to_string()
+        )
+    );
+
+    //  递归深度在限制之内时可以正常求值
+    let program = "
+      (define (count n) (if (= n 0) 0 (+ 1 (count (- n 1)))))
+      (count 5000)
+      ";
+    let result = eval_program(program, env).unwrap();
+    assert_eq!(result.to_string(), "5000");
+}
+
+//  运行时构造的 car 方向深层嵌套 list，不经过 parser 的嵌套限制
+#[test]
+fn deep_runtime_structures() {
+    let env = Rc::new(RefCell::new(Env::new()));
+    let program = "
+      (define (nest n acc)
+        (if (= n 0) acc (nest (- n 1) (list acc))))
+      (define (f) (+ 1 1))
+      (define a (nest 100000 (list f)))
+      (define b (nest 100000 (list f)))
+      (list (= a b)
+            (= (list f a) (list f b))
+            (= (eval (list 'quasiquote a)) b))
+      ";
+    let result = eval_program(program, env.clone()).unwrap();
+    assert_eq!(result.to_string(), "(true true true)");
+
+    //  显示不会栈溢出
+    let result = eval_program("a", env.clone()).unwrap();
+    assert!(result.to_string().starts_with("(((("));
+
+    //  释放深层嵌套的向量、box 和哈希表不会栈溢出
+    let program = "
+      (define (wrap n acc make)
+        (if (= n 0) acc (wrap (- n 1) (make acc) make)))
+      (define (in-hash value)
+        (let ((h (make-hash)))
+          (begin (hash-set! h 'k value) h)))
+      (list (vector-length (wrap 20000 1 vector))
+            (length (list (wrap 20000 1 box)))
+            (length (list (wrap 20000 1 in-hash))))
+      ";
+    let result = eval_program(program, env).unwrap();
+    assert_eq!(result.to_string(), "(1 1 1)");
+}
+
+//  包含自身的容器互相比较时，沿着环回到原处的一对容器视为相等
+#[test]
+fn cyclic_containers() {
+    let env = Rc::new(RefCell::new(Env::new()));
+    let program = "
+      (define v (vector 1 2))
+      (vector-set! v 0 v)
+      (define w (vector 1 2))
+      (vector-set! w 0 w)
+      (define u (vector 1 3))
+      (vector-set! u 0 u)
+      (define b (box 1))
+      (set-box! b (list 1 b))
+      (define c (box 1))
+      (set-box! c (list 1 c))
+      (define h (make-hash))
+      (hash-set! h 'self h)
+      (define g (make-hash))
+      (hash-set! g 'self g)
+      (list (= (list v) (list w))
+            (= (list v) (list u))
+            (= (list b) (list c))
+            (= (list h) (list g)))
+      ";
+    let result = eval_program(program, env).unwrap();
+    assert_eq!(result.to_string(), "(true false true true)");
+}
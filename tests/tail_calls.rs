@@ -0,0 +1,110 @@
+//  尾位置的调用不占用栈空间，循环次数远超求值器的递归深度限制（10000 层）
+//  没有尾调用优化时这些循环会报 "Maximum recursion depth exceeded"
+use std::{cell::RefCell, rc::Rc};
+
+use rlisp::env::Env;
+use rlisp::eval::eval_program;
+
+const N: usize = 100_000;
+
+fn run(program: &str) -> String {
+    let env = Rc::new(RefCell::new(Env::new()));
+    let program = program.replace("N", &N.to_string());
+    match eval_program(&program, env) {
+        Ok(value) => value.to_string(),
+        Err(err) => panic!("{}", err),
+    }
+}
+
+#[test]
+fn tail_call_in_if() {
+    let program = "
+      (define (loop n acc)
+        (if (= n 0) acc (loop (- n 1) (+ acc 1))))
+      (loop N 0)
+      ";
+    assert_eq!(run(program), N.to_string());
+}
+
+//  没有 else 的 if
+#[test]
+fn tail_call_in_one_armed_if() {
+    let program = "
+      (define count 0)
+      (define (loop n)
+        (set! count (+ count 1))
+        (if (> n 0) (loop (- n 1))))
+      (loop N)
+      count
+      ";
+    assert_eq!(run(program), (N + 1).to_string());
+}
+
+#[test]
+fn tail_call_in_cond() {
+    let program = "
+      (define count 0)
+      (define (loop n)
+        (cond ((= n 0) count)
+              ((< n 0) 'negative)
+              (else (set! count (+ count 1)) (loop (- n 1)))))
+      (loop N)
+      ";
+    assert_eq!(run(program), N.to_string());
+}
+
+#[test]
+fn tail_call_in_let() {
+    let program = "
+      (define (loop n)
+        (let ((m (- n 1)))
+          (define done (< m 0))
+          (if done 'done (loop m))))
+      (loop N)
+      ";
+    assert_eq!(run(program), "done");
+}
+
+#[test]
+fn tail_call_in_begin() {
+    let program = "
+      (define count 0)
+      (define (loop n)
+        (begin
+          (set! count (+ count 1))
+          (if (= n 0) count (loop (- n 1)))))
+      (loop N)
+      ";
+    assert_eq!(run(program), (N + 1).to_string());
+}
+
+#[test]
+fn tail_call_in_and() {
+    let program = "
+      (define (loop n)
+        (and (> n -1) (if (= n 0) 'done (loop (- n 1)))))
+      (loop N)
+      ";
+    assert_eq!(run(program), "done");
+}
+
+#[test]
+fn tail_call_in_or() {
+    let program = "
+      (define (loop n)
+        (or (= n 0) (loop (- n 1))))
+      (loop N)
+      ";
+    assert_eq!(run(program), "true");
+}
+
+//  相互调用的过程同样不占用栈空间
+#[test]
+fn mutual_tail_calls() {
+    let program = "
+      (define (even? n) (if (= n 0) true (odd? (- n 1))))
+      (define (odd? n) (if (= n 0) false (even? (- n 1))))
+      (even? N)
+      ";
+    assert_eq!(run(program), "true");
+}